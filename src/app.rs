@@ -1,13 +1,19 @@
 use crate::{
-  render::{Camera, RenderLoop},
-  Keys, Pressed, Released, Scene, CAMERA_FAST_SPEED, CAMERA_NORMAL_SPEED,
+  game_objects::{Cube, ObjectHandle, Renderable3dObject, SceneObject},
+  input::{
+    Action, AxisResponse, GamepadEvent, GamepadState, Gamepads, Input, InputMap, InputState,
+  },
+  render::{Camera, RenderError, RenderLoop, RenderSettings},
+  Scene, CAMERA_FAST_SPEED, CAMERA_NORMAL_SPEED, GAMEPAD_LOOK_SPEED,
 };
 use cgmath::Point3;
+use log::{info, warn};
 use std::time::Duration;
 use winit::{
   dpi::{LogicalSize, PhysicalPosition},
-  event::{ElementState, VirtualKeyCode},
+  event::{ElementState, ModifiersState},
   event_loop::EventLoop,
+  window::Window,
 };
 
 pub struct Mouse {
@@ -27,17 +33,28 @@ struct Screen {
 pub struct App {
   render_loop: RenderLoop,
   scene: Scene,
-  keys: Keys,
+  input: InputState,
+  gamepads: Gamepads,
+  gamepad: GamepadState,
   camera: Camera,
   mouse: Mouse,
   screen: Screen,
+  /// Objects spawned while running, in the order they were added
+  spawned: Vec<ObjectHandle>,
+  /// Moved with the keyboard, the first cube of the scene if there is any
+  controlled_cube: Option<ObjectHandle>,
+  /// Moved with the keyboard, the first square of the scene if there is any
+  controlled_square: Option<ObjectHandle>,
 }
 
 impl App {
-  pub fn start(event_loop: &EventLoop<()>) -> Self {
-    let scene = Scene::load();
-
-    let render_loop = RenderLoop::new(event_loop, &scene);
+  pub fn start(
+    event_loop: &EventLoop<()>,
+    scene: Scene,
+    input_map: InputMap,
+    settings: &RenderSettings,
+  ) -> Result<Self, RenderError> {
+    let render_loop = RenderLoop::new(event_loop, &scene, settings)?;
 
     // initial window configuration
     let window = render_loop.get_window();
@@ -53,7 +70,7 @@ impl App {
       y: window_dimensions.height as f32 / 2.0,
     };
 
-    let camera = Camera::new(
+    let mut camera = Camera::new(
       Point3 {
         x: 0.0,
         y: 0.0,
@@ -63,11 +80,17 @@ impl App {
       0.8,
       aspect_ratio,
     );
+    camera.set_reverse_z(settings.reverse_z);
+
+    let controlled_cube = scene.cube_handles().first().copied();
+    let controlled_square = scene.square_handles().first().copied();
 
-    Self {
+    Ok(Self {
       render_loop,
       scene,
-      keys: Keys::default(),
+      input: InputState::new(input_map),
+      gamepads: Gamepads::new(),
+      gamepad: GamepadState::new(AxisResponse::default()),
       camera,
       screen: Screen {
         middle: middle_position,
@@ -78,115 +101,149 @@ impl App {
         in_window: false,
         getting_grabbed: false,
       },
-    }
+      spawned: Vec::new(),
+      controlled_cube,
+      controlled_square,
+    })
   }
 
-  pub fn update(&mut self, delta_time: &Duration) {
-    self.camera.handle_keys(&self.keys, delta_time);
+  pub fn update(&mut self, delta_time: &Duration) -> Result<(), RenderError> {
+    for event in self.gamepads.poll() {
+      self.handle_gamepad_event(event);
+    }
+
+    self.camera.speed = if self.input.is_active(Action::Sprint) || self.gamepad.sprint() {
+      CAMERA_FAST_SPEED
+    } else {
+      CAMERA_NORMAL_SPEED
+    };
+    self.camera.handle_input(&self.input, delta_time);
+    self.camera.fly(self.gamepad.movement(), delta_time);
     self.camera.handle_mouse_movement(&self.mouse);
+    let [yaw, pitch] = self.gamepad.look();
+    let look_amount = GAMEPAD_LOOK_SPEED * delta_time.as_secs_f32();
+    self.camera.rotate(yaw * look_amount, pitch * look_amount);
     self.mouse.delta_x = 0.0;
     self.mouse.delta_y = 0.0;
 
     self.update_square_position((delta_time.as_micros() as f32) / 1000000.0);
 
-    self.render_loop.update(&self.camera, &self.scene);
+    self.render_loop.update(&self.camera, &self.scene)?;
+    // the render loop remembers which frames in flight still need to be updated
+    self.scene.objects_changed = false;
+    Ok(())
   }
 
   fn update_square_position(&mut self, delta_seconds: f32) {
-    if self.keys.up_key == Pressed && self.keys.s == Released {
-      self.scene.get_square_mut(0).move_up(delta_seconds)
+    let input = &self.input;
+    let square = match self
+      .controlled_square
+      .and_then(|handle| self.scene.get_square_mut(handle))
+    {
+      Some(square) => square,
+      None => return,
+    };
+
+    if input.is_active(Action::MoveSquareUp) && !input.is_active(Action::MoveBackward) {
+      square.move_up(delta_seconds)
     }
-    if self.keys.down_key == Pressed && self.keys.w == Released {
-      self.scene.get_square_mut(0).move_down(delta_seconds)
+    if input.is_active(Action::MoveSquareDown) && !input.is_active(Action::MoveForward) {
+      square.move_down(delta_seconds)
     }
-    if self.keys.left_key == Pressed && self.keys.d == Released {
-      self.scene.get_square_mut(0).move_left(delta_seconds)
+    if input.is_active(Action::MoveSquareLeft) && !input.is_active(Action::MoveRight) {
+      square.move_left(delta_seconds)
     }
-    if self.keys.right_key == Pressed && self.keys.a == Released {
-      self.scene.get_square_mut(0).move_right(delta_seconds)
+    if input.is_active(Action::MoveSquareRight) && !input.is_active(Action::MoveLeft) {
+      square.move_right(delta_seconds)
     }
   }
 
-  pub fn handle_keyboard_input(&mut self, key_code: VirtualKeyCode, state: ElementState) -> bool {
-    let state = match state {
-      ElementState::Pressed => Pressed,
-      ElementState::Released => Released,
-    };
+  /// Moves the cube controlled with the keyboard, if the scene has one
+  fn move_cube(&mut self, move_object: impl FnOnce(&mut Renderable3dObject)) {
+    if let Some(object) = self
+      .controlled_cube
+      .and_then(|handle| self.scene.get_object_mut(handle))
+    {
+      move_object(object);
+    }
+  }
 
-    match key_code {
-      VirtualKeyCode::W => self.keys.w = state,
-      VirtualKeyCode::A => self.keys.a = state,
-      VirtualKeyCode::S => self.keys.s = state,
-      VirtualKeyCode::D => self.keys.d = state,
-      VirtualKeyCode::Space => self.keys.space = state,
-      VirtualKeyCode::LControl => self.keys.l_ctrl = state,
-      VirtualKeyCode::LShift => {
-        if state == Pressed {
-          self.camera.speed = CAMERA_FAST_SPEED;
-        } else {
-          self.camera.speed = CAMERA_NORMAL_SPEED;
-        }
+  /// Handles a key or mouse button, returning true if the program should exit
+  pub fn handle_input(&mut self, input: Input, state: ElementState) -> Result<bool, RenderError> {
+    for action in self.input.handle_input(input, state) {
+      if self.trigger(action)? {
+        return Ok(true);
       }
-      VirtualKeyCode::Up => self.keys.up_key = state,
-      VirtualKeyCode::Down => self.keys.down_key = state,
-      VirtualKeyCode::Left => self.keys.left_key = state,
-      VirtualKeyCode::Right => self.keys.right_key = state,
-      VirtualKeyCode::Escape => return true,
-      _ => {}
     }
 
-    if state == Released {
-      match key_code {
-        VirtualKeyCode::C => {
-          self.toggle_cursor_grab();
-        }
-        VirtualKeyCode::Numpad8 => {
-          self
-            .scene
-            .get_cube_mut(0)
-            .as_renderable()
-            .move_relative_x(1.0);
-        }
-        VirtualKeyCode::Numpad2 => {
-          self
-            .scene
-            .get_cube_mut(0)
-            .as_renderable()
-            .move_relative_x(-1.0);
-        }
-        VirtualKeyCode::Numpad4 => {
-          self
-            .scene
-            .get_cube_mut(0)
-            .as_renderable()
-            .move_relative_z(-1.0);
-        }
-        VirtualKeyCode::Numpad6 => {
-          self
-            .scene
-            .get_cube_mut(0)
-            .as_renderable()
-            .move_relative_z(1.0);
-        }
-        VirtualKeyCode::Numpad9 => {
-          self
-            .scene
-            .get_cube_mut(0)
-            .as_renderable()
-            .move_relative_y(-1.0);
-        }
-        VirtualKeyCode::Numpad3 => {
-          self
-            .scene
-            .get_cube_mut(0)
-            .as_renderable()
-            .move_relative_y(1.0);
+    Ok(false)
+  }
+
+  /// Applies a gamepad event in the same way as the ones read from connected gamepads,
+  /// which allows driving the camera without real hardware
+  pub fn handle_gamepad_event(&mut self, event: GamepadEvent) {
+    self.gamepad.handle_event(event);
+  }
+
+  pub fn handle_modifiers(&mut self, modifiers: ModifiersState) {
+    self.input.handle_modifiers(modifiers);
+  }
+
+  /// Does what a triggered action should do, returning true if the program should exit.
+  /// Actions that are only checked while held are ignored.
+  fn trigger(&mut self, action: Action) -> Result<bool, RenderError> {
+    match action {
+      Action::Exit => return Ok(true),
+      Action::ToggleGrab => {
+        self.toggle_cursor_grab();
+      }
+      Action::ToggleVsync => {
+        let present_mode = self.render_loop.toggle_vsync()?;
+        info!("Present mode: {:?}", present_mode);
+      }
+      #[cfg(debug_assertions)]
+      Action::SimulateDeviceLoss => self.render_loop.inject_error(RenderError::DeviceLost),
+      #[cfg(debug_assertions)]
+      Action::SimulateSurfaceLoss => self.render_loop.inject_error(RenderError::SurfaceLost),
+      #[cfg(not(debug_assertions))]
+      Action::SimulateDeviceLoss | Action::SimulateSurfaceLoss => {
+        warn!("Losing the device or the surface can only be simulated in debug builds")
+      }
+      Action::NextViewMode => {
+        let view_mode = self.render_loop.next_view_mode();
+        info!("View mode: {:?}", view_mode);
+      }
+      Action::SpawnCube => {
+        let position = self.camera.position + self.camera.front * 5.0;
+        let mut cube = Cube::new(position);
+        cube.change_to_random_color();
+        self.spawned.push(self.scene.spawn(SceneObject::Cube(cube)));
+      }
+      Action::DespawnCube => {
+        if let Some(handle) = self.spawned.pop() {
+          self.scene.despawn(handle);
         }
-        _ => {}
       }
+      Action::MoveCubePositiveX => self.move_cube(|cube| cube.move_relative_x(1.0)),
+      Action::MoveCubeNegativeX => self.move_cube(|cube| cube.move_relative_x(-1.0)),
+      Action::MoveCubeNegativeZ => self.move_cube(|cube| cube.move_relative_z(-1.0)),
+      Action::MoveCubePositiveZ => self.move_cube(|cube| cube.move_relative_z(1.0)),
+      Action::MoveCubeNegativeY => self.move_cube(|cube| cube.move_relative_y(-1.0)),
+      Action::MoveCubePositiveY => self.move_cube(|cube| cube.move_relative_y(1.0)),
+      Action::MoveForward
+      | Action::MoveBackward
+      | Action::MoveLeft
+      | Action::MoveRight
+      | Action::MoveUp
+      | Action::MoveDown
+      | Action::Sprint
+      | Action::MoveSquareUp
+      | Action::MoveSquareDown
+      | Action::MoveSquareLeft
+      | Action::MoveSquareRight => {}
     }
 
-    false
+    Ok(false)
   }
 
   pub fn handle_window_resize(&mut self) {
@@ -200,11 +257,7 @@ impl App {
         self.mouse.delta_x += position.x as f32 - self.screen.middle.x;
         self.mouse.delta_y += position.y as f32 - self.screen.middle.y;
       }
-      self
-        .render_loop
-        .get_window()
-        .set_cursor_position(self.screen.middle)
-        .unwrap();
+      center_cursor(self.render_loop.get_window(), self.screen.middle);
     }
   }
 
@@ -215,8 +268,8 @@ impl App {
   pub fn handle_cursor_entered_window(&mut self) {
     if self.mouse.getting_grabbed {
       let window = self.render_loop.get_window();
-      window.set_cursor_grab(true).unwrap();
-      window.set_cursor_position(self.screen.middle).unwrap();
+      set_cursor_grab(window, true);
+      center_cursor(window, self.screen.middle);
       self.mouse.delta_x = 0.0;
       self.mouse.delta_y = 0.0;
     }
@@ -225,8 +278,7 @@ impl App {
 
   pub fn handle_cursor_left_window(&mut self) {
     if self.mouse.getting_grabbed {
-      let window = self.render_loop.get_window();
-      window.set_cursor_grab(false).unwrap();
+      set_cursor_grab(self.render_loop.get_window(), false);
     }
 
     self.mouse.in_window = false;
@@ -237,15 +289,17 @@ impl App {
     if self.mouse.getting_grabbed {
       self.mouse.getting_grabbed = false;
 
-      window.set_cursor_grab(false).unwrap();
+      set_cursor_grab(window, false);
       window.set_cursor_visible(true);
     } else {
       self.mouse.getting_grabbed = true;
 
-      window.set_cursor_grab(true).unwrap();
+      // the camera still works when the platform can't grab the cursor, as it gets centered
+      // after every movement
+      set_cursor_grab(window, true);
       window.set_cursor_visible(false);
 
-      window.set_cursor_position(self.screen.middle).unwrap();
+      center_cursor(window, self.screen.middle);
       self.mouse.delta_x = 0.0;
       self.mouse.delta_y = 0.0;
     }
@@ -256,3 +310,16 @@ impl App {
     window_size.width as f32 / window_size.height as f32
   }
 }
+
+/// Grabbing isn't supported on every platform, so failing only prints a warning
+fn set_cursor_grab(window: &Window, grab: bool) {
+  if let Err(e) = window.set_cursor_grab(grab) {
+    warn!("Failed to change cursor grab: {}", e);
+  }
+}
+
+fn center_cursor(window: &Window, middle: PhysicalPosition<f32>) {
+  if let Err(e) = window.set_cursor_position(middle) {
+    warn!("Failed to move cursor: {}", e);
+  }
+}
@@ -0,0 +1,148 @@
+use crate::render::{DeviceChoice, RenderSettings};
+use vulkano::swapchain::PresentMode;
+
+/// Enables validation when set to anything other than 0, same as `--validation`
+const VALIDATION_ENV_VAR: &str = "VULKANO_VALIDATION";
+
+/// Command line options, parsed manually from `std::env::args`
+#[derive(Default)]
+pub struct Args {
+  /// If set, renders a single frame without a window and saves it to this path
+  pub headless_output: Option<String>,
+  /// Size of the image rendered in headless mode
+  pub headless_size: [u32; 2],
+  /// Scene file to load instead of generating the default scene
+  pub scene: Option<String>,
+  /// Paths where the loaded scene gets saved to before starting
+  pub save_scene: Vec<String>,
+  /// OBJ files that get added to the scene, each with a single object
+  pub obj_models: Vec<String>,
+  /// glTF files whose meshes and nodes get added to the scene
+  pub gltf_files: Vec<String>,
+  /// Bindings file to load instead of using the default bindings
+  pub bindings: Option<String>,
+  /// Paths where the loaded bindings get saved to before starting
+  pub save_bindings: Vec<String>,
+  /// If set, prints the available devices and exits
+  pub list_devices: bool,
+  /// Options used when creating the window renderer
+  pub render_settings: RenderSettings,
+}
+
+impl Args {
+  pub fn parse() -> Self {
+    let mut args = Args {
+      headless_size: [800, 600],
+      ..Default::default()
+    };
+
+    if let Some(value) = std::env::var_os(VALIDATION_ENV_VAR) {
+      args.render_settings.validation = value != "0";
+    }
+
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+      match arg.as_str() {
+        "--headless" => {
+          args.headless_output = Some(iter.next().expect("--headless requires an output path"));
+        }
+        "--size" => {
+          let value = iter.next().expect("--size requires a value like 800x600");
+          args.headless_size = parse_size(&value).unwrap_or_else(|| {
+            panic!(
+              "invalid size \"{}\", expected something like 800x600",
+              value
+            )
+          });
+        }
+        "--scene" => {
+          args.scene = Some(iter.next().expect("--scene requires a scene file path"));
+        }
+        "--save-scene" => {
+          args
+            .save_scene
+            .push(iter.next().expect("--save-scene requires an output path"));
+        }
+        "--obj" => {
+          args
+            .obj_models
+            .push(iter.next().expect("--obj requires a model file path"));
+        }
+        "--gltf" => {
+          args
+            .gltf_files
+            .push(iter.next().expect("--gltf requires a glTF file path"));
+        }
+        "--bindings" => {
+          args.bindings = Some(
+            iter
+              .next()
+              .expect("--bindings requires a bindings file path"),
+          );
+        }
+        "--save-bindings" => {
+          args.save_bindings.push(
+            iter
+              .next()
+              .expect("--save-bindings requires an output path"),
+          );
+        }
+        "--frames-in-flight" => {
+          let value = iter.next().expect("--frames-in-flight requires a number");
+          args.render_settings.frames_in_flight = match value.parse() {
+            Ok(count) if count > 0 => count,
+            _ => panic!(
+              "invalid frames in flight \"{}\", expected a number bigger than 0",
+              value
+            ),
+          };
+        }
+        "--present-mode" => {
+          let value = iter
+            .next()
+            .expect("--present-mode requires fifo, mailbox or immediate");
+          args.render_settings.present_mode = parse_present_mode(&value).unwrap_or_else(|| {
+            panic!(
+              "invalid present mode \"{}\", expected fifo, mailbox or immediate",
+              value
+            )
+          });
+        }
+        "--hdr" => args.render_settings.hdr = true,
+        "--reverse-z" => args.render_settings.reverse_z = true,
+        "--validation" => args.render_settings.validation = true,
+        "--list-devices" => args.list_devices = true,
+        "--device" => {
+          let value = iter
+            .next()
+            .expect("--device requires an index or part of a device name");
+          args.render_settings.device = Some(DeviceChoice::parse(&value));
+        }
+        "--msaa" => {
+          let value = iter.next().expect("--msaa requires a sample count");
+          args.render_settings.msaa_samples = match value.parse() {
+            Ok(samples @ (1 | 2 | 4 | 8)) => samples,
+            _ => panic!("invalid sample count \"{}\", expected 1, 2, 4 or 8", value),
+          };
+        }
+        _ => panic!("unknown argument \"{}\"", arg),
+      }
+    }
+
+    args
+  }
+}
+
+fn parse_size(value: &str) -> Option<[u32; 2]> {
+  let (width, height) = value.split_once('x')?;
+  Some([width.parse().ok()?, height.parse().ok()?])
+}
+
+fn parse_present_mode(value: &str) -> Option<PresentMode> {
+  match value {
+    "fifo" => Some(PresentMode::Fifo),
+    "mailbox" => Some(PresentMode::Mailbox),
+    "immediate" => Some(PresentMode::Immediate),
+    _ => None,
+  }
+}
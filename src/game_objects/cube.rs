@@ -1,26 +1,33 @@
 use cgmath::{Matrix4, Point3};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-use crate::game_objects::{Renderable3dObject, RenderableIn3d};
+use crate::game_objects::{Material, Renderable3dObject, RenderableIn3d};
 
+#[derive(Serialize, Deserialize)]
 pub struct Cube {
+  /// RON scene files written before cubes had colors get the default one
+  #[serde(default = "default_color")]
+  pub color: [f32; 3],
   object: Renderable3dObject,
 }
 
 impl Cube {
   pub fn new(position: Point3<f32>) -> Self {
-    Self {
-      object: Renderable3dObject::new(position),
-    }
+    Self::from_full(Renderable3dObject::new(position))
   }
 
   pub fn from_full(object: Renderable3dObject) -> Self {
-    Self { object }
+    Self {
+      color: default_color(),
+      object,
+    }
   }
 
-  // pub fn change_to_random_color(&mut self) {
-  //   let get_random_float = || rand::thread_rng().gen_range(0..100) as f32 / 100.0;
-  //   self.color = [get_random_float(), get_random_float(), get_random_float()];
-  // }
+  pub fn change_to_random_color(&mut self) {
+    let get_random_float = || rand::thread_rng().gen_range(0..100) as f32 / 100.0;
+    self.color = [get_random_float(), get_random_float(), get_random_float()];
+  }
 
   pub fn as_renderable(&mut self) -> &mut Renderable3dObject {
     &mut self.object
@@ -31,4 +38,12 @@ impl RenderableIn3d for Cube {
   fn get_model_matrix(&self) -> Matrix4<f32> {
     self.object.get_model_matrix()
   }
+
+  fn get_material(&self) -> Material {
+    Material::with_color(self.color)
+  }
+}
+
+fn default_color() -> [f32; 3] {
+  [0.27, 0.27, 0.27]
 }
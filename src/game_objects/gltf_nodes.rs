@@ -0,0 +1,69 @@
+use crate::{
+  game_objects::{LoadedModel, ModelSource, Renderable3dObject},
+  render::ModelLoadError,
+};
+use cgmath::{Matrix4, SquareMatrix};
+use gltf::{Gltf, Node};
+use std::path::Path;
+
+/// Converts the node hierarchy of the default scene of a glTF file into objects.
+///
+/// Returns one [`LoadedModel`] for every mesh that is used by at least one node, where
+/// each object is a node with the transformations of all its parents applied.
+/// Fails if any of them can't be represented by an object, like non uniform scales.
+pub fn load_gltf_nodes(path: &Path) -> Result<Vec<LoadedModel>, ModelLoadError> {
+  let gltf = Gltf::open(path)?;
+
+  let mut models: Vec<LoadedModel> = gltf
+    .meshes()
+    .map(|mesh| {
+      LoadedModel::new(ModelSource::Gltf {
+        path: path.to_path_buf(),
+        mesh: mesh.index(),
+      })
+    })
+    .collect();
+
+  if let Some(scene) = gltf.default_scene().or_else(|| gltf.scenes().next()) {
+    for node in scene.nodes() {
+      add_node(&node, Matrix4::identity(), &mut models)?;
+    }
+  }
+
+  Ok(
+    models
+      .into_iter()
+      .filter(|model| !model.objects.is_empty())
+      .collect(),
+  )
+}
+
+fn add_node(
+  node: &Node,
+  parent_transform: Matrix4<f32>,
+  models: &mut Vec<LoadedModel>,
+) -> Result<(), ModelLoadError> {
+  let transform = parent_transform * Matrix4::from(node.transform().matrix());
+
+  if let Some(mesh) = node.mesh() {
+    // glTF is y-up while this project is y-down (see `GltfModel`), so the transform
+    // gets converted by negating y before and after it
+    let flip_y = Matrix4::from_nonuniform_scale(1.0, -1.0, 1.0);
+    let object = Renderable3dObject::from_matrix(flip_y * transform * flip_y).map_err(|error| {
+      ModelLoadError::NodeTransform {
+        node: node
+          .name()
+          .map(str::to_string)
+          .unwrap_or_else(|| node.index().to_string()),
+        error,
+      }
+    })?;
+    models[mesh.index()].objects.push(object);
+  }
+
+  for child in node.children() {
+    add_node(&child, transform, models)?;
+  }
+
+  Ok(())
+}
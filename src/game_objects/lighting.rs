@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+
+/// Number of point lights that get sent to the gpu, others are ignored
+pub const MAX_POINT_LIGHTS: usize = 4;
+
+/// Light that reaches every object from the same direction, like the sun
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DirectionalLight {
+  /// Direction the light travels in (remember that y points down)
+  pub direction: [f32; 3],
+  pub color: [f32; 3],
+  pub ambient: f32,
+  pub diffuse: f32,
+  pub specular: f32,
+}
+
+/// Light coming from a single position, that gets weaker with distance
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PointLight {
+  pub position: [f32; 3],
+  pub color: [f32; 3],
+  pub ambient: f32,
+  pub diffuse: f32,
+  pub specular: f32,
+  /// Quadratic attenuation factor, bigger values make the light reach less far
+  pub attenuation: f32,
+}
+
+/// All lights in the scene
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Lighting {
+  pub directional: DirectionalLight,
+  /// Only the first [`MAX_POINT_LIGHTS`] are used
+  pub point_lights: Vec<PointLight>,
+}
+
+impl Default for Lighting {
+  fn default() -> Self {
+    Self {
+      directional: DirectionalLight {
+        direction: [-0.4, 1.0, -0.3],
+        color: [1.0, 1.0, 1.0],
+        ambient: 0.15,
+        diffuse: 0.7,
+        specular: 0.3,
+      },
+      point_lights: vec![PointLight {
+        position: [2.0, -2.0, 2.0],
+        color: [1.0, 0.85, 0.6],
+        ambient: 0.0,
+        diffuse: 1.0,
+        specular: 0.5,
+        attenuation: 0.2,
+      }],
+    }
+  }
+}
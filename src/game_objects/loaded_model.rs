@@ -0,0 +1,36 @@
+use crate::game_objects::{Material, Renderable3dObject};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// File that a model gets loaded from
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ModelSource {
+  /// Wavefront OBJ file. Uses only the object / group with this name if it is set,
+  /// otherwise all of them merged together.
+  Obj {
+    path: PathBuf,
+    group: Option<String>,
+  },
+  /// Mesh with this index from a glTF 2.0 file (`.gltf` or `.glb`)
+  Gltf { path: PathBuf, mesh: usize },
+}
+
+/// A model loaded from a file and all objects that get drawn with it
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LoadedModel {
+  pub source: ModelSource,
+  /// Shared by every object, white keeps the texture colors unchanged
+  #[serde(default)]
+  pub material: Material,
+  pub objects: Vec<Renderable3dObject>,
+}
+
+impl LoadedModel {
+  pub fn new(source: ModelSource) -> Self {
+    Self {
+      source,
+      material: Material::default(),
+      objects: Vec::new(),
+    }
+  }
+}
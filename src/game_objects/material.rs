@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+/// Surface properties of an object, used when lighting it
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Material {
+  /// Base color, multiplied with the texture if the model has one
+  pub color: [f32; 3],
+  /// Multiplies the specular intensity of every light
+  pub specular: f32,
+  /// Blinn-Phong exponent, bigger values make highlights smaller and sharper
+  pub shininess: f32,
+}
+
+impl Material {
+  pub fn with_color(color: [f32; 3]) -> Self {
+    Self {
+      color,
+      ..Default::default()
+    }
+  }
+}
+
+impl Default for Material {
+  fn default() -> Self {
+    Self {
+      color: [1.0, 1.0, 1.0],
+      specular: 1.0,
+      shininess: 32.0,
+    }
+  }
+}
@@ -2,9 +2,19 @@
 //! special functions for rendering
 
 mod cube;
+mod gltf_nodes;
+mod lighting;
+mod loaded_model;
+mod material;
+mod object_handle;
 mod renderable_3d_object;
 mod square;
 
 pub use cube::Cube;
-pub use renderable_3d_object::{Renderable3dObject, RenderableIn3d};
+pub use gltf_nodes::load_gltf_nodes;
+pub use lighting::{DirectionalLight, Lighting, PointLight, MAX_POINT_LIGHTS};
+pub use loaded_model::{LoadedModel, ModelSource};
+pub use material::Material;
+pub use object_handle::{ObjectHandle, ObjectHandles, ObjectLocation, SceneObject};
+pub use renderable_3d_object::{Renderable3dObject, RenderableIn3d, TransformError};
 pub use square::Square;
@@ -0,0 +1,175 @@
+use crate::game_objects::{Cube, Renderable3dObject, Square};
+use std::collections::HashMap;
+
+/// Identifies a single object in the scene.
+///
+/// Stays valid until the object gets despawned, even if other objects get added or removed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ObjectHandle(u64);
+
+/// Object that can be spawned into the scene
+pub enum SceneObject {
+  Cube(Cube),
+  Square(Square),
+  /// Object drawn with the model at this index in `Scene::loaded_models`
+  Loaded {
+    model: usize,
+    object: Renderable3dObject,
+  },
+}
+
+/// Where an object is currently stored in the scene
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ObjectLocation {
+  Cube(usize),
+  Square(usize),
+  Loaded { model: usize, index: usize },
+}
+
+impl ObjectLocation {
+  pub fn index(&self) -> usize {
+    match *self {
+      ObjectLocation::Cube(i) | ObjectLocation::Square(i) => i,
+      ObjectLocation::Loaded { index, .. } => index,
+    }
+  }
+}
+
+/// Keeps track of the handle of every object in the scene.
+///
+/// Objects get removed with `swap_remove`, so the last object of the same list moves into the
+/// place of the removed one and only its location needs to be updated.
+#[derive(Default)]
+pub struct ObjectHandles {
+  next: u64,
+  locations: HashMap<ObjectHandle, ObjectLocation>,
+  cubes: Vec<ObjectHandle>,
+  squares: Vec<ObjectHandle>,
+  loaded: Vec<Vec<ObjectHandle>>,
+}
+
+impl ObjectHandles {
+  /// Creates a handle for an object that was pushed to the end of its list
+  pub fn push(&mut self, location: ObjectLocation) -> ObjectHandle {
+    let handle = ObjectHandle(self.next);
+    self.next += 1;
+
+    let list = self.list_mut(location);
+    debug_assert_eq!(list.len(), location.index());
+    list.push(handle);
+    self.locations.insert(handle, location);
+
+    handle
+  }
+
+  /// Removes a handle in the same way as the object gets removed from its list,
+  /// returning where the object was
+  pub fn swap_remove(&mut self, handle: ObjectHandle) -> Option<ObjectLocation> {
+    let location = self.locations.remove(&handle)?;
+
+    let list = self.list_mut(location);
+    list.swap_remove(location.index());
+    let moved = list.get(location.index()).copied();
+    if let Some(moved) = moved {
+      self.locations.insert(moved, location);
+    }
+
+    Some(location)
+  }
+
+  pub fn get(&self, handle: ObjectHandle) -> Option<ObjectLocation> {
+    self.locations.get(&handle).copied()
+  }
+
+  /// Handles of every cube, in the same order as the cubes
+  pub fn cubes(&self) -> &[ObjectHandle] {
+    &self.cubes
+  }
+
+  /// Handles of every square, in the same order as the squares
+  pub fn squares(&self) -> &[ObjectHandle] {
+    &self.squares
+  }
+
+  fn list_mut(&mut self, location: ObjectLocation) -> &mut Vec<ObjectHandle> {
+    match location {
+      ObjectLocation::Cube(_) => &mut self.cubes,
+      ObjectLocation::Square(_) => &mut self.squares,
+      ObjectLocation::Loaded { model, .. } => {
+        if self.loaded.len() <= model {
+          self.loaded.resize_with(model + 1, Vec::new);
+        }
+        &mut self.loaded[model]
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Pushes `count` cubes, returning their handles in order
+  fn push_cubes(handles: &mut ObjectHandles, count: usize) -> Vec<ObjectHandle> {
+    (0..count)
+      .map(|i| handles.push(ObjectLocation::Cube(i)))
+      .collect()
+  }
+
+  #[test]
+  fn despawning_last_object_moves_nothing() {
+    let mut handles = ObjectHandles::default();
+    let cubes = push_cubes(&mut handles, 3);
+
+    assert_eq!(handles.swap_remove(cubes[2]), Some(ObjectLocation::Cube(2)));
+
+    assert_eq!(handles.get(cubes[0]), Some(ObjectLocation::Cube(0)));
+    assert_eq!(handles.get(cubes[1]), Some(ObjectLocation::Cube(1)));
+    assert_eq!(handles.cubes(), &cubes[..2]);
+  }
+
+  #[test]
+  fn despawning_middle_object_moves_last_one_into_its_slot() {
+    let mut handles = ObjectHandles::default();
+    let cubes = push_cubes(&mut handles, 4);
+
+    assert_eq!(handles.swap_remove(cubes[1]), Some(ObjectLocation::Cube(1)));
+
+    assert_eq!(handles.get(cubes[0]), Some(ObjectLocation::Cube(0)));
+    assert_eq!(handles.get(cubes[3]), Some(ObjectLocation::Cube(1)));
+    assert_eq!(handles.get(cubes[2]), Some(ObjectLocation::Cube(2)));
+    assert_eq!(handles.cubes(), &[cubes[0], cubes[3], cubes[2]]);
+  }
+
+  #[test]
+  fn stale_handle_returns_none() {
+    let mut handles = ObjectHandles::default();
+    let cubes = push_cubes(&mut handles, 2);
+    handles.swap_remove(cubes[0]);
+
+    assert_eq!(handles.get(cubes[0]), None);
+    assert_eq!(handles.swap_remove(cubes[0]), None);
+    // a new object in the same slot gets a different handle
+    let new = handles.push(ObjectLocation::Cube(1));
+    assert_ne!(new, cubes[0]);
+    assert_eq!(handles.get(cubes[0]), None);
+    assert_eq!(handles.get(cubes[1]), Some(ObjectLocation::Cube(0)));
+  }
+
+  #[test]
+  fn lists_are_separate() {
+    let mut handles = ObjectHandles::default();
+    let cube = handles.push(ObjectLocation::Cube(0));
+    let square = handles.push(ObjectLocation::Square(0));
+    let loaded = [0, 1].map(|index| handles.push(ObjectLocation::Loaded { model: 1, index }));
+
+    handles.swap_remove(loaded[0]);
+
+    assert_eq!(handles.get(cube), Some(ObjectLocation::Cube(0)));
+    assert_eq!(handles.get(square), Some(ObjectLocation::Square(0)));
+    assert_eq!(
+      handles.get(loaded[1]),
+      Some(ObjectLocation::Loaded { model: 1, index: 0 })
+    );
+  }
+}
@@ -1,12 +1,28 @@
-use crate::other::add_points;
-use cgmath::{EuclideanSpace, Euler, Matrix4, Point3, Rad};
+use crate::{game_objects::Material, other::add_points};
+use cgmath::{
+  EuclideanSpace, Euler, InnerSpace, Matrix3, Matrix4, Point3, Quaternion, Rad, SquareMatrix,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// How much the scales of different axes can differ, relative to the largest one,
+/// for a transformation to still count as uniformly scaled
+const UNIFORM_SCALE_TOLERANCE: f32 = 1e-3;
 
 pub trait RenderableIn3d {
   fn get_model_matrix(&self) -> Matrix4<f32>;
+
+  fn get_material(&self) -> Material {
+    Material::default()
+  }
 }
 
 /// Object information suitable for rendering in 3D. Caches certain matrices
 /// in order to perform less calculations while rendering.
+///
+/// Only position, rotation and scale get serialized, the matrices are recalculated when loading.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "Renderable3dObjectData", into = "Renderable3dObjectData")]
 pub struct Renderable3dObject {
   position: Point3<f32>,
   translation_matrix: Matrix4<f32>,
@@ -57,6 +73,42 @@ impl Renderable3dObject {
     }
   }
 
+  /// Decomposes a transformation matrix without shear into position, rotation and scale.
+  ///
+  /// Only uniform scales can be represented (shaders transform normals and bounding spheres
+  /// with the model matrix), so non uniform and mirrored ones get rejected.
+  pub fn from_matrix(matrix: Matrix4<f32>) -> Result<Self, TransformError> {
+    let position = Point3::from_vec(matrix.w.truncate());
+
+    let axes = [
+      matrix.x.truncate(),
+      matrix.y.truncate(),
+      matrix.z.truncate(),
+    ];
+    // a negative determinant means that an odd number of axes got flipped
+    if Matrix3::from_cols(axes[0], axes[1], axes[2]).determinant() < 0.0 {
+      return Err(TransformError::Mirrored);
+    }
+
+    let scales = axes.map(|axis| axis.magnitude());
+    let max = scales.iter().copied().fold(f32::MIN, f32::max);
+    let min = scales.iter().copied().fold(f32::MAX, f32::min);
+    if max - min > max * UNIFORM_SCALE_TOLERANCE {
+      return Err(TransformError::NonUniformScale(scales));
+    }
+    // scales only differ because of rounding errors
+    let scale = (scales[0] + scales[1] + scales[2]) / 3.0;
+
+    let rotation_matrix = Matrix3::from_cols(
+      axes[0] / scales[0],
+      axes[1] / scales[1],
+      axes[2] / scales[2],
+    );
+    let rotation = Euler::from(Quaternion::from(rotation_matrix));
+
+    Ok(Self::from_full(position, rotation, scale))
+  }
+
   pub fn get_position(&self) -> Point3<f32> {
     self.position
   }
@@ -157,3 +209,104 @@ impl RenderableIn3d for Renderable3dObject {
     self.model_matrix
   }
 }
+
+/// Reasons why a transformation matrix can't be turned into a [`Renderable3dObject`]
+#[derive(Debug)]
+pub enum TransformError {
+  /// Scales along the x, y and z axes, which are not the same
+  NonUniformScale([f32; 3]),
+  /// The transformation flips the object, which would also make its back faces visible
+  Mirrored,
+}
+
+impl fmt::Display for TransformError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      TransformError::NonUniformScale([x, y, z]) => write!(
+        f,
+        "non uniform scale ({}, {}, {}) is not supported, only the same scale on every axis",
+        x, y, z
+      ),
+      TransformError::Mirrored => write!(f, "mirrored transformations are not supported"),
+    }
+  }
+}
+
+/// Serialized form of [`Renderable3dObject`]
+#[derive(Serialize, Deserialize)]
+struct Renderable3dObjectData {
+  position: [f32; 3],
+  /// Euler angles in radians
+  rotation: [f32; 3],
+  scale: f32,
+}
+
+impl From<Renderable3dObject> for Renderable3dObjectData {
+  fn from(object: Renderable3dObject) -> Self {
+    Self {
+      position: object.position.into(),
+      rotation: [
+        object.rotation.x.0,
+        object.rotation.y.0,
+        object.rotation.z.0,
+      ],
+      scale: object.scale,
+    }
+  }
+}
+
+impl From<Renderable3dObjectData> for Renderable3dObject {
+  fn from(data: Renderable3dObjectData) -> Self {
+    let [x, y, z] = data.rotation;
+    Renderable3dObject::from_full(
+      data.position.into(),
+      Euler::new(Rad(x), Rad(y), Rad(z)),
+      data.scale,
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use cgmath::{Deg, Vector3};
+
+  #[test]
+  fn decomposes_uniform_scale() {
+    let rotation = Euler::new(Rad(0.3), Rad(-0.2), Rad(1.1));
+    let matrix = Matrix4::from_translation(Vector3::new(1.0, -2.0, 3.0))
+      * Matrix4::from(rotation)
+      * Matrix4::from_scale(2.0);
+
+    let object = Renderable3dObject::from_matrix(matrix).unwrap();
+
+    assert_eq!(object.get_position(), Point3::new(1.0, -2.0, 3.0));
+    assert!((object.get_scale() - 2.0).abs() < 1e-5);
+    let model_matrix = object.get_model_matrix();
+    for (column, expected) in [0, 1, 2, 3].map(|i| (model_matrix[i], matrix[i])) {
+      assert!((column - expected).magnitude() < 1e-5);
+    }
+  }
+
+  #[test]
+  fn rejects_non_uniform_scale() {
+    let matrix = Matrix4::from_angle_y(Deg(45.0)) * Matrix4::from_nonuniform_scale(1.0, 2.0, 1.0);
+
+    match Renderable3dObject::from_matrix(matrix) {
+      Err(TransformError::NonUniformScale(scales)) => {
+        assert!((scales[1] - 2.0).abs() < 1e-5)
+      }
+      _ => panic!("non uniform scale should be rejected"),
+    }
+  }
+
+  #[test]
+  fn rejects_mirrored() {
+    let matrix = Matrix4::from_nonuniform_scale(-1.0, 1.0, 1.0);
+
+    assert!(matches!(
+      Renderable3dObject::from_matrix(matrix),
+      Err(TransformError::Mirrored)
+    ));
+  }
+}
@@ -1,7 +1,9 @@
-use crate::game_objects::{Renderable3dObject, RenderableIn3d};
+use crate::game_objects::{Material, Renderable3dObject, RenderableIn3d};
 use cgmath::{Matrix4, Point3};
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
+#[derive(Serialize, Deserialize)]
 pub struct Square {
   pub color: [f32; 3],
   pub object: Renderable3dObject,
@@ -51,4 +53,8 @@ impl RenderableIn3d for Square {
   fn get_model_matrix(&self) -> Matrix4<f32> {
     self.object.get_model_matrix()
   }
+
+  fn get_material(&self) -> Material {
+    Material::with_color(self.color)
+  }
 }
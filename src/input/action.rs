@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+
+/// Something that the user can do, bound to inputs by [`crate::input::InputMap`].
+///
+/// Movement actions and [`Action::Sprint`] are active while one of their bindings is held,
+/// the rest get triggered once when a binding gets released, see [`Action::triggers_on_press`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+  MoveForward,
+  MoveBackward,
+  MoveLeft,
+  MoveRight,
+  MoveUp,
+  MoveDown,
+  Sprint,
+  ToggleGrab,
+  ToggleVsync,
+  NextViewMode,
+  SpawnCube,
+  DespawnCube,
+  MoveSquareUp,
+  MoveSquareDown,
+  MoveSquareLeft,
+  MoveSquareRight,
+  MoveCubePositiveX,
+  MoveCubeNegativeX,
+  MoveCubePositiveY,
+  MoveCubeNegativeY,
+  MoveCubePositiveZ,
+  MoveCubeNegativeZ,
+  /// Only does something in debug builds, where it's bound by default
+  SimulateDeviceLoss,
+  /// Only does something in debug builds, where it's bound by default
+  SimulateSurfaceLoss,
+  Exit,
+}
+
+impl Action {
+  /// Exiting happens as soon as a binding gets pressed, other triggered actions wait for it to be
+  /// released
+  pub fn triggers_on_press(&self) -> bool {
+    matches!(self, Action::Exit)
+  }
+}
@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+use winit::event::{ModifiersState, MouseButton, VirtualKeyCode};
+
+/// Physical input that can be pressed and released
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Input {
+  Key(VirtualKeyCode),
+  Mouse(MouseButton),
+}
+
+/// Modifier keys that have to be held for a binding to match.
+/// Modifiers that are not required can be held or not, but when several bindings of the same
+/// input match, only the ones requiring the most modifiers are used, see [`Modifiers::count`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Modifiers {
+  pub shift: bool,
+  pub ctrl: bool,
+  pub alt: bool,
+  pub logo: bool,
+}
+
+impl Modifiers {
+  /// True if every modifier required by `self` is held in `held`
+  pub fn are_held(&self, held: &Modifiers) -> bool {
+    (!self.shift || held.shift)
+      && (!self.ctrl || held.ctrl)
+      && (!self.alt || held.alt)
+      && (!self.logo || held.logo)
+  }
+
+  /// Number of required modifiers, bindings with more of them are more specific
+  pub fn count(&self) -> usize {
+    [self.shift, self.ctrl, self.alt, self.logo]
+      .into_iter()
+      .filter(|&required| required)
+      .count()
+  }
+}
+
+impl From<ModifiersState> for Modifiers {
+  fn from(state: ModifiersState) -> Self {
+    Self {
+      shift: state.shift(),
+      ctrl: state.ctrl(),
+      alt: state.alt(),
+      logo: state.logo(),
+    }
+  }
+}
+
+/// Input that activates an action, written as `(input: Key(W))` or
+/// `(input: Mouse(Left), modifiers: (ctrl: true))` in bindings files
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Binding {
+  pub input: Input,
+  #[serde(default)]
+  pub modifiers: Modifiers,
+}
+
+impl Binding {
+  pub fn key(key: VirtualKeyCode) -> Self {
+    Self {
+      input: Input::Key(key),
+      modifiers: Modifiers::default(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn required_modifiers_have_to_be_held() {
+    let ctrl = Modifiers {
+      ctrl: true,
+      ..Modifiers::default()
+    };
+    let ctrl_shift = Modifiers {
+      shift: true,
+      ..ctrl
+    };
+
+    assert!(Modifiers::default().are_held(&ctrl));
+    assert!(ctrl.are_held(&ctrl_shift));
+    assert!(!ctrl_shift.are_held(&ctrl));
+    assert_eq!(ctrl_shift.count(), 2);
+  }
+}
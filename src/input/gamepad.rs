@@ -0,0 +1,291 @@
+use cgmath::Vector3;
+use gilrs::{Axis, Button, EventType, Gilrs};
+use log::warn;
+
+/// Analog input of a gamepad, sticks go from -1 to 1 with up and right being positive and
+/// triggers go from 0 to 1
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GamepadAxis {
+  LeftStickX,
+  LeftStickY,
+  RightStickX,
+  RightStickY,
+  LeftTrigger,
+  RightTrigger,
+}
+
+/// Gamepad input used by the camera, independent from gilrs so that it can also be created by hand
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GamepadEvent {
+  Axis(GamepadAxis, f32),
+  Sprint(bool),
+  /// Resets everything, so that the camera doesn't keep moving
+  Disconnected,
+}
+
+/// Dead zone and response curve applied to every axis.
+///
+/// Values inside the dead zone become 0 and the rest get rescaled to start from 0, then raised
+/// to `exponent` so that small movements are more precise.
+#[derive(Clone, Copy, Debug)]
+pub struct AxisResponse {
+  pub dead_zone: f32,
+  pub exponent: f32,
+}
+
+impl Default for AxisResponse {
+  fn default() -> Self {
+    Self {
+      dead_zone: 0.15,
+      exponent: 2.0,
+    }
+  }
+}
+
+impl AxisResponse {
+  pub fn apply(&self, value: f32) -> f32 {
+    let magnitude = value.abs().min(1.0);
+    if magnitude <= self.dead_zone {
+      return 0.0;
+    }
+
+    let scaled = (magnitude - self.dead_zone) / (1.0 - self.dead_zone);
+    scaled.powf(self.exponent).copysign(value)
+  }
+
+  /// Same as [`AxisResponse::apply`], but uses the distance from the center of the stick,
+  /// so that moving diagonally isn't affected by the dead zone of each axis
+  pub fn apply_stick(&self, [x, y]: [f32; 2]) -> [f32; 2] {
+    let magnitude = (x * x + y * y).sqrt();
+    if magnitude <= self.dead_zone {
+      return [0.0, 0.0];
+    }
+
+    let scale = self.apply(magnitude.min(1.0)) / magnitude;
+    [x * scale, y * scale]
+  }
+}
+
+/// Latest state of the gamepad, changed by [`GamepadEvent`]s
+#[derive(Debug, Default)]
+pub struct GamepadState {
+  response: AxisResponse,
+  left_stick: [f32; 2],
+  right_stick: [f32; 2],
+  left_trigger: f32,
+  right_trigger: f32,
+  sprint: bool,
+}
+
+impl GamepadState {
+  pub fn new(response: AxisResponse) -> Self {
+    Self {
+      response,
+      ..Default::default()
+    }
+  }
+
+  pub fn handle_event(&mut self, event: GamepadEvent) {
+    match event {
+      GamepadEvent::Axis(axis, value) => match axis {
+        GamepadAxis::LeftStickX => self.left_stick[0] = value,
+        GamepadAxis::LeftStickY => self.left_stick[1] = value,
+        GamepadAxis::RightStickX => self.right_stick[0] = value,
+        GamepadAxis::RightStickY => self.right_stick[1] = value,
+        GamepadAxis::LeftTrigger => self.left_trigger = value,
+        GamepadAxis::RightTrigger => self.right_trigger = value,
+      },
+      GamepadEvent::Sprint(pressed) => self.sprint = pressed,
+      GamepadEvent::Disconnected => *self = Self::new(self.response),
+    }
+  }
+
+  /// Movement in the same format as [`crate::render::Camera::fly`].
+  /// The left stick moves horizontally, the right trigger goes up and the left one down.
+  pub fn movement(&self) -> Vector3<f32> {
+    let [x, z] = self.response.apply_stick(self.left_stick);
+    let y = self.response.apply(self.right_trigger) - self.response.apply(self.left_trigger);
+    Vector3::new(x, y, z)
+  }
+
+  /// Yaw and pitch directions from the right stick, pushing it up looks up
+  pub fn look(&self) -> [f32; 2] {
+    let [x, y] = self.response.apply_stick(self.right_stick);
+    [x, -y]
+  }
+
+  pub fn sprint(&self) -> bool {
+    self.sprint
+  }
+}
+
+/// Reads events of every connected gamepad through gilrs
+pub struct Gamepads {
+  /// None if gilrs isn't supported on this platform
+  gilrs: Option<Gilrs>,
+}
+
+impl Gamepads {
+  /// Gamepads get disabled with a message if gilrs fails to start
+  pub fn new() -> Self {
+    let gilrs = match Gilrs::new() {
+      Ok(gilrs) => Some(gilrs),
+      Err(e) => {
+        warn!("Gamepads disabled: {}", e);
+        None
+      }
+    };
+
+    Self { gilrs }
+  }
+
+  /// Returns the events received since the last call, ignoring the ones that the camera doesn't use
+  pub fn poll(&mut self) -> Vec<GamepadEvent> {
+    let mut events = Vec::new();
+    if let Some(gilrs) = &mut self.gilrs {
+      while let Some(event) = gilrs.next_event() {
+        events.extend(convert_event(event.event));
+      }
+    }
+    events
+  }
+}
+
+impl Default for Gamepads {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+fn convert_event(event: EventType) -> Option<GamepadEvent> {
+  match event {
+    EventType::AxisChanged(axis, value, _) => {
+      let axis = match axis {
+        Axis::LeftStickX => GamepadAxis::LeftStickX,
+        Axis::LeftStickY => GamepadAxis::LeftStickY,
+        Axis::RightStickX => GamepadAxis::RightStickX,
+        Axis::RightStickY => GamepadAxis::RightStickY,
+        Axis::LeftZ => GamepadAxis::LeftTrigger,
+        Axis::RightZ => GamepadAxis::RightTrigger,
+        _ => return None,
+      };
+      Some(GamepadEvent::Axis(axis, value))
+    }
+    // most gamepads report triggers as analog buttons
+    EventType::ButtonChanged(Button::LeftTrigger2, value, _) => {
+      Some(GamepadEvent::Axis(GamepadAxis::LeftTrigger, value))
+    }
+    EventType::ButtonChanged(Button::RightTrigger2, value, _) => {
+      Some(GamepadEvent::Axis(GamepadAxis::RightTrigger, value))
+    }
+    EventType::ButtonPressed(Button::LeftThumb, _) => Some(GamepadEvent::Sprint(true)),
+    EventType::ButtonReleased(Button::LeftThumb, _) => Some(GamepadEvent::Sprint(false)),
+    EventType::Disconnected => Some(GamepadEvent::Disconnected),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const EPSILON: f32 = 1e-5;
+
+  fn state_after(events: &[GamepadEvent]) -> GamepadState {
+    let mut state = GamepadState::new(AxisResponse::default());
+    for &event in events {
+      state.handle_event(event);
+    }
+    state
+  }
+
+  fn assert_close(actual: f32, expected: f32) {
+    assert!(
+      (actual - expected).abs() < EPSILON,
+      "expected {}, got {}",
+      expected,
+      actual
+    );
+  }
+
+  #[test]
+  fn dead_zone_returns_zero() {
+    let state = state_after(&[
+      GamepadEvent::Axis(GamepadAxis::LeftStickX, 0.1),
+      GamepadEvent::Axis(GamepadAxis::RightStickY, -0.15),
+      GamepadEvent::Axis(GamepadAxis::RightTrigger, 0.05),
+    ]);
+
+    assert_eq!(state.movement(), Vector3::new(0.0, 0.0, 0.0));
+    assert_eq!(state.look(), [0.0, 0.0]);
+  }
+
+  #[test]
+  fn full_deflection_returns_one() {
+    let state = state_after(&[
+      GamepadEvent::Axis(GamepadAxis::LeftStickX, 1.0),
+      GamepadEvent::Axis(GamepadAxis::RightStickX, -1.0),
+    ]);
+
+    assert_close(state.movement().x, 1.0);
+    assert_close(state.look()[0], -1.0);
+  }
+
+  #[test]
+  fn curve_keeps_sign() {
+    let response = AxisResponse::default();
+    for value in [0.3, 0.5, 0.8] {
+      let positive = response.apply(value);
+      let negative = response.apply(-value);
+
+      assert!(positive > 0.0 && positive < value);
+      assert_close(negative, -positive);
+    }
+  }
+
+  #[test]
+  fn dead_zone_is_radial_on_diagonals() {
+    let response = AxisResponse::default();
+
+    // each axis is inside the dead zone, but the stick as a whole isn't
+    let [x, y] = response.apply_stick([0.12, 0.12]);
+    assert!(x > 0.0 && y > 0.0);
+    assert_eq!(response.apply(0.12), 0.0);
+
+    assert_eq!(response.apply_stick([0.1, -0.1]), [0.0, 0.0]);
+
+    let diagonal = std::f32::consts::FRAC_1_SQRT_2;
+    let [x, y] = response.apply_stick([diagonal, -diagonal]);
+    assert_close(x, diagonal);
+    assert_close(y, -diagonal);
+  }
+
+  #[test]
+  fn triggers_move_vertically() {
+    let up = state_after(&[GamepadEvent::Axis(GamepadAxis::RightTrigger, 1.0)]);
+    let down = state_after(&[GamepadEvent::Axis(GamepadAxis::LeftTrigger, 1.0)]);
+    let both = state_after(&[
+      GamepadEvent::Axis(GamepadAxis::RightTrigger, 1.0),
+      GamepadEvent::Axis(GamepadAxis::LeftTrigger, 1.0),
+    ]);
+
+    assert_eq!(up.movement(), Vector3::new(0.0, 1.0, 0.0));
+    assert_eq!(down.movement(), Vector3::new(0.0, -1.0, 0.0));
+    assert_eq!(both.movement(), Vector3::new(0.0, 0.0, 0.0));
+  }
+
+  #[test]
+  fn disconnected_resets_state() {
+    let state = state_after(&[
+      GamepadEvent::Axis(GamepadAxis::LeftStickY, 1.0),
+      GamepadEvent::Axis(GamepadAxis::RightStickX, 1.0),
+      GamepadEvent::Axis(GamepadAxis::LeftTrigger, 1.0),
+      GamepadEvent::Sprint(true),
+      GamepadEvent::Disconnected,
+    ]);
+
+    assert_eq!(state.movement(), Vector3::new(0.0, 0.0, 0.0));
+    assert_eq!(state.look(), [0.0, 0.0]);
+    assert!(!state.sprint());
+  }
+}
@@ -0,0 +1,189 @@
+use crate::input::{Action, Binding};
+use std::{
+  collections::BTreeMap,
+  fmt,
+  fs::File,
+  io::{BufReader, BufWriter},
+  path::Path,
+};
+use winit::event::VirtualKeyCode;
+
+#[derive(Debug)]
+pub enum InputMapError {
+  Io(std::io::Error),
+  Serialize(ron::Error),
+  Deserialize(ron::de::SpannedError),
+}
+
+impl fmt::Display for InputMapError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      InputMapError::Io(e) => write!(f, "io error: {}", e),
+      InputMapError::Serialize(e) => write!(f, "failed to write bindings: {}", e),
+      InputMapError::Deserialize(e) => write!(f, "invalid bindings: {}", e),
+    }
+  }
+}
+
+impl From<std::io::Error> for InputMapError {
+  fn from(e: std::io::Error) -> Self {
+    InputMapError::Io(e)
+  }
+}
+
+/// Bindings of every action, each action can have any number of them.
+///
+/// Saved as a RON map from actions to lists of bindings. Actions missing from a loaded file
+/// keep their default bindings, and an empty list unbinds an action.
+#[derive(Clone, Debug)]
+pub struct InputMap {
+  bindings: BTreeMap<Action, Vec<Binding>>,
+}
+
+impl InputMap {
+  /// Loads bindings previously saved with [`InputMap::save_to`] or written by hand
+  pub fn load_from(path: impl AsRef<Path>) -> Result<Self, InputMapError> {
+    let reader = BufReader::new(File::open(path)?);
+    let loaded = ron::de::from_reader(reader).map_err(InputMapError::Deserialize)?;
+    Ok(Self::with_overrides(loaded))
+  }
+
+  /// Same as [`InputMap::load_from`], from the contents of a bindings file
+  pub fn from_ron(bindings: &str) -> Result<Self, InputMapError> {
+    let loaded = ron::de::from_str(bindings).map_err(InputMapError::Deserialize)?;
+    Ok(Self::with_overrides(loaded))
+  }
+
+  /// Default bindings, with the ones of every loaded action replaced
+  fn with_overrides(loaded: BTreeMap<Action, Vec<Binding>>) -> Self {
+    let mut map = Self::default();
+    map.bindings.extend(loaded);
+    map
+  }
+
+  pub fn save_to(&self, path: impl AsRef<Path>) -> Result<(), InputMapError> {
+    let writer = BufWriter::new(File::create(path)?);
+    ron::ser::to_writer_pretty(writer, &self.bindings, ron::ser::PrettyConfig::new())
+      .map_err(InputMapError::Serialize)
+  }
+
+  pub fn get(&self, action: Action) -> &[Binding] {
+    self
+      .bindings
+      .get(&action)
+      .map(|bindings| bindings.as_slice())
+      .unwrap_or(&[])
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = (Action, &Binding)> {
+    self
+      .bindings
+      .iter()
+      .flat_map(|(&action, bindings)| bindings.iter().map(move |binding| (action, binding)))
+  }
+}
+
+impl Default for InputMap {
+  /// QWERTY bindings, the same ones listed in the README.
+  /// Simulating device and surface loss is only bound in debug builds.
+  fn default() -> Self {
+    use Action::*;
+    use VirtualKeyCode as Key;
+
+    let debug_bindings: &[(Action, Key)] = if cfg!(debug_assertions) {
+      &[(SimulateDeviceLoss, Key::L), (SimulateSurfaceLoss, Key::K)]
+    } else {
+      &[]
+    };
+
+    let bindings = [
+      (MoveForward, Key::W),
+      (MoveBackward, Key::S),
+      (MoveLeft, Key::A),
+      (MoveRight, Key::D),
+      (MoveUp, Key::Space),
+      (MoveDown, Key::LControl),
+      (Sprint, Key::LShift),
+      (ToggleGrab, Key::C),
+      (ToggleVsync, Key::V),
+      (NextViewMode, Key::M),
+      (SpawnCube, Key::N),
+      (DespawnCube, Key::Back),
+      (MoveSquareUp, Key::Up),
+      (MoveSquareDown, Key::Down),
+      (MoveSquareLeft, Key::Left),
+      (MoveSquareRight, Key::Right),
+      (MoveCubePositiveX, Key::Numpad8),
+      (MoveCubeNegativeX, Key::Numpad2),
+      (MoveCubePositiveY, Key::Numpad3),
+      (MoveCubeNegativeY, Key::Numpad9),
+      (MoveCubePositiveZ, Key::Numpad6),
+      (MoveCubeNegativeZ, Key::Numpad4),
+      (Exit, Key::Escape),
+    ]
+    .into_iter()
+    .chain(debug_bindings.iter().copied())
+    .map(|(action, key)| (action, vec![Binding::key(key)]))
+    .collect();
+
+    Self { bindings }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::input::{Input, Modifiers};
+  use winit::event::MouseButton;
+
+  #[test]
+  fn parses_keys_mouse_buttons_and_modifiers() {
+    let map = InputMap::from_ron(
+      "{SpawnCube: [(input: Key(Z)), (input: Mouse(Right), modifiers: (ctrl: true, alt: true))]}",
+    )
+    .unwrap();
+
+    assert_eq!(
+      map.get(Action::SpawnCube),
+      [
+        Binding::key(VirtualKeyCode::Z),
+        Binding {
+          input: Input::Mouse(MouseButton::Right),
+          modifiers: Modifiers {
+            ctrl: true,
+            alt: true,
+            ..Modifiers::default()
+          },
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn missing_actions_keep_defaults_and_empty_lists_unbind() {
+    let map = InputMap::from_ron("{Exit: []}").unwrap();
+
+    assert!(map.get(Action::Exit).is_empty());
+    assert_eq!(
+      map.get(Action::ToggleGrab),
+      InputMap::default().get(Action::ToggleGrab)
+    );
+  }
+
+  #[test]
+  fn saved_bindings_load_back() {
+    let default = InputMap::default();
+    let saved = ron::ser::to_string(&default.bindings).unwrap();
+    let loaded = InputMap::from_ron(&saved).unwrap();
+
+    assert!(default.iter().eq(loaded.iter()));
+  }
+
+  #[test]
+  fn rejects_unknown_actions() {
+    assert!(matches!(
+      InputMap::from_ron("{Fly: [(input: Key(F))]}"),
+      Err(InputMapError::Deserialize(_))
+    ));
+  }
+}
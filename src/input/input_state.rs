@@ -0,0 +1,181 @@
+use crate::input::{Action, Binding, Input, InputMap, Modifiers};
+use std::collections::HashMap;
+use winit::event::{ElementState, ModifiersState};
+
+/// Keeps track of held inputs in order to tell which actions are active
+pub struct InputState {
+  map: InputMap,
+  /// Held inputs, with the modifiers that were held when they got pressed
+  held: HashMap<Input, Modifiers>,
+  modifiers: Modifiers,
+}
+
+impl InputState {
+  pub fn new(map: InputMap) -> Self {
+    Self {
+      map,
+      held: HashMap::new(),
+      modifiers: Modifiers::default(),
+    }
+  }
+
+  /// Records an input, returning the actions that it triggered.
+  /// Actions get triggered once when their binding gets released, using the modifiers that were
+  /// held when it got pressed, except for the ones in [`Action::triggers_on_press`].
+  pub fn handle_input(&mut self, input: Input, state: ElementState) -> Vec<Action> {
+    let (modifiers, on_press) = match state {
+      ElementState::Pressed => {
+        // key repeat sends presses for a key that is already held
+        if self.held.contains_key(&input) {
+          return Vec::new();
+        }
+        self.held.insert(input, self.modifiers);
+        (self.modifiers, true)
+      }
+      ElementState::Released => match self.held.remove(&input) {
+        Some(modifiers) => (modifiers, false),
+        // pressed before the window got focus
+        None => return Vec::new(),
+      },
+    };
+
+    self
+      .matching_bindings(input, &modifiers)
+      .map(|(action, _)| action)
+      .filter(|action| action.triggers_on_press() == on_press)
+      .collect()
+  }
+
+  pub fn handle_modifiers(&mut self, state: ModifiersState) {
+    self.modifiers = state.into();
+  }
+
+  /// True while any binding of the action is held
+  pub fn is_active(&self, action: Action) -> bool {
+    self.held.keys().any(|&input| {
+      self
+        .matching_bindings(input, &self.modifiers)
+        .any(|(a, _)| a == action)
+    })
+  }
+
+  /// Bindings of the input whose modifiers are held, keeping only the most specific ones so that
+  /// Ctrl+W doesn't also count as W
+  fn matching_bindings<'a>(
+    &'a self,
+    input: Input,
+    modifiers: &'a Modifiers,
+  ) -> impl Iterator<Item = (Action, &'a Binding)> + 'a {
+    let matches =
+      move |binding: &Binding| binding.input == input && binding.modifiers.are_held(modifiers);
+    let most_specific = self
+      .map
+      .iter()
+      .filter(|(_, binding)| matches(binding))
+      .map(|(_, binding)| binding.modifiers.count())
+      .max();
+
+    self.map.iter().filter(move |(_, binding)| {
+      matches(binding) && Some(binding.modifiers.count()) == most_specific
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use winit::event::{MouseButton, VirtualKeyCode};
+
+  fn input_state(bindings: &str) -> InputState {
+    InputState::new(InputMap::from_ron(bindings).unwrap())
+  }
+
+  #[test]
+  fn held_action_is_active_until_released() {
+    let mut input = input_state("{MoveForward: [(input: Key(W))]}");
+    let key = Input::Key(VirtualKeyCode::W);
+
+    assert!(!input.is_active(Action::MoveForward));
+    assert!(input.handle_input(key, ElementState::Pressed).is_empty());
+    assert!(input.is_active(Action::MoveForward));
+    input.handle_input(key, ElementState::Released);
+    assert!(!input.is_active(Action::MoveForward));
+  }
+
+  #[test]
+  fn triggered_action_fires_once_on_release() {
+    let mut input = input_state("{SpawnCube: [(input: Mouse(Left))]}");
+    let button = Input::Mouse(MouseButton::Left);
+
+    assert!(input.handle_input(button, ElementState::Pressed).is_empty());
+    // key repeat
+    assert!(input.handle_input(button, ElementState::Pressed).is_empty());
+    assert_eq!(
+      input.handle_input(button, ElementState::Released),
+      vec![Action::SpawnCube]
+    );
+    assert!(input
+      .handle_input(button, ElementState::Released)
+      .is_empty());
+  }
+
+  #[test]
+  fn exit_fires_on_press() {
+    let mut input = input_state("{Exit: [(input: Key(Escape))]}");
+    let key = Input::Key(VirtualKeyCode::Escape);
+
+    assert_eq!(
+      input.handle_input(key, ElementState::Pressed),
+      vec![Action::Exit]
+    );
+    assert!(input.handle_input(key, ElementState::Released).is_empty());
+  }
+
+  #[test]
+  fn most_specific_binding_wins() {
+    let mut input = input_state(
+      "{MoveForward: [(input: Key(W))], SpawnCube: [(input: Key(W), modifiers: (ctrl: true))]}",
+    );
+    let key = Input::Key(VirtualKeyCode::W);
+
+    input.handle_modifiers(ModifiersState::CTRL);
+    input.handle_input(key, ElementState::Pressed);
+    assert!(!input.is_active(Action::MoveForward));
+    assert_eq!(
+      input.handle_input(key, ElementState::Released),
+      vec![Action::SpawnCube]
+    );
+
+    input.handle_modifiers(ModifiersState::empty());
+    input.handle_input(key, ElementState::Pressed);
+    assert!(input.is_active(Action::MoveForward));
+    assert!(!input
+      .handle_input(key, ElementState::Released)
+      .contains(&Action::SpawnCube));
+  }
+
+  #[test]
+  fn unrequired_modifiers_are_ignored() {
+    let mut input = input_state("{MoveForward: [(input: Key(W))]}");
+
+    input.handle_modifiers(ModifiersState::SHIFT);
+    input.handle_input(Input::Key(VirtualKeyCode::W), ElementState::Pressed);
+    assert!(input.is_active(Action::MoveForward));
+  }
+
+  #[test]
+  fn released_binding_uses_modifiers_from_press() {
+    let mut input = input_state(
+      "{NextViewMode: [(input: Key(M))], SpawnCube: [(input: Key(M), modifiers: (ctrl: true))]}",
+    );
+    let key = Input::Key(VirtualKeyCode::M);
+
+    input.handle_modifiers(ModifiersState::CTRL);
+    input.handle_input(key, ElementState::Pressed);
+    input.handle_modifiers(ModifiersState::empty());
+    assert_eq!(
+      input.handle_input(key, ElementState::Released),
+      vec![Action::SpawnCube]
+    );
+  }
+}
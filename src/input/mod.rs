@@ -0,0 +1,15 @@
+//! Turns keyboard and mouse input into actions, so that controls can be changed with a
+//! bindings file instead of being tied to physical keys.
+//! Gamepads are read separately, as their sticks and triggers are analog.
+
+mod action;
+mod binding;
+mod gamepad;
+mod input_map;
+mod input_state;
+
+pub use action::Action;
+pub use binding::{Binding, Input, Modifiers};
+pub use gamepad::{AxisResponse, GamepadAxis, GamepadEvent, GamepadState, Gamepads};
+pub use input_map::{InputMap, InputMapError};
+pub use input_state::InputState;
@@ -5,20 +5,23 @@
 
 
 pub mod app;
+mod args;
 pub mod game_objects;
-mod keys;
+pub mod input;
 pub mod other;
 pub mod render;
 mod scene;
 
-pub use keys::{
-  KeyState::{Pressed, Released},
-  Keys,
-};
-
 pub use scene::Scene;
 pub use app::App;
 
+use args::Args;
+use cgmath::Point3;
+use game_objects::Renderable3dObject;
+use input::{Input, InputMap};
+use log::{error, info};
+use render::{Camera, HeadlessRenderer, RenderError, RenderSettings};
+
 use std::time::{Duration, Instant};
 use winit::{
   event::{Event, MouseScrollDelta, WindowEvent},
@@ -38,14 +41,48 @@ const MILLIS_BETWEEN_RESIZING: u64 = 50;
 
 pub const CAMERA_NORMAL_SPEED: f32 = 2.0;
 pub const CAMERA_FAST_SPEED: f32 = 10.0;
+/// Radians per second that the camera turns with the right stick of a gamepad pushed all the way
+pub const GAMEPAD_LOOK_SPEED: f32 = 2.5;
+
+/// Default number of frames that can be processed by the gpu at the same time, unless changed with
+/// `--frames-in-flight`. More frames can increase framerate, but also input latency and memory usage.
+pub const FRAMES_IN_FLIGHT: usize = 2;
 
 /// Will generate value.pow(3) cubes around the camera if not None
 pub const GENERATE_CUBES: Option<usize> = Some(64);
 
 /// Contains the main event loop and matches events that get handled by [`App`]
 fn main() {
+  env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+  let args = Args::parse();
+  if args.list_devices {
+    if let Err(e) = render::print_devices() {
+      exit_with_error(e);
+    }
+    return;
+  }
+
+  let scene = load_scene(&args);
+  let input_map = load_input_map(&args);
+
+  if let Some(output_path) = &args.headless_output {
+    if let Err(e) = render_headless(
+      scene,
+      output_path,
+      args.headless_size,
+      &args.render_settings,
+    ) {
+      exit_with_error(e);
+    }
+    return;
+  }
+
   let event_loop = EventLoop::new();
-  let mut app = App::start(&event_loop);
+  let mut app = match App::start(&event_loop, scene, input_map, &args.render_settings) {
+    Ok(app) => app,
+    Err(e) => exit_with_error(e),
+  };
   let mut draw_next_frame = true;
   let mut time_to_resume_drawing = Duration::from_millis(0);
 
@@ -72,11 +109,25 @@ fn main() {
       ..
     } => {
       if let Some(key_code) = input.virtual_keycode {
-        if app.handle_keyboard_input(key_code, input.state) {
-          *control_flow = ControlFlow::Exit;
+        match app.handle_input(Input::Key(key_code), input.state) {
+          Ok(true) => *control_flow = ControlFlow::Exit,
+          Ok(false) => {}
+          Err(e) => exit_with_error(e),
         }
       }
     }
+    Event::WindowEvent {
+      event: WindowEvent::MouseInput { state, button, .. },
+      ..
+    } => match app.handle_input(Input::Mouse(button), state) {
+      Ok(true) => *control_flow = ControlFlow::Exit,
+      Ok(false) => {}
+      Err(e) => exit_with_error(e),
+    },
+    Event::WindowEvent {
+      event: WindowEvent::ModifiersChanged(modifiers),
+      ..
+    } => app.handle_modifiers(modifiers),
     Event::WindowEvent {
       event: WindowEvent::CursorMoved { position, .. },
       ..
@@ -111,7 +162,9 @@ fn main() {
       let delta_time = this_frame_time - previous_frame_time;
 
       if draw_next_frame {
-        app.update(&delta_time);
+        if let Err(e) = app.update(&delta_time) {
+          exit_with_error(e);
+        }
       } else {
         if time_to_resume_drawing > delta_time {
           time_to_resume_drawing -= delta_time;
@@ -125,7 +178,7 @@ fn main() {
           time_until_next_fps_print -= delta_time;
         } else {
           time_until_next_fps_print = Duration::from_millis(MILLIS_BETWEEN_FPS_PRINTS);
-          println!("fps: {}", 1000000.0 / ((delta_time).as_micros() as f32));
+          info!("fps: {}", 1000000.0 / ((delta_time).as_micros() as f32));
         }
       }
 
@@ -134,3 +187,87 @@ fn main() {
     _ => (),
   });
 }
+
+/// Loads the scene file passed in the arguments (or the default scene), adds requested models
+/// and saves it to every requested path
+fn load_scene(args: &Args) -> Scene {
+  let mut scene = match &args.scene {
+    Some(path) => {
+      Scene::load_from(path).unwrap_or_else(|e| panic!("Failed to load scene \"{}\": {}", path, e))
+    }
+    None => Scene::load(),
+  };
+
+  for path in args.obj_models.iter() {
+    scene
+      .import_obj(path, Renderable3dObject::new(Point3::new(0.0, 0.0, -3.0)))
+      .unwrap_or_else(|e| panic!("Failed to import OBJ file \"{}\": {}", path, e));
+  }
+
+  for path in args.gltf_files.iter() {
+    scene
+      .import_gltf(path)
+      .unwrap_or_else(|e| panic!("Failed to import glTF file \"{}\": {}", path, e));
+  }
+
+  for path in args.save_scene.iter() {
+    scene
+      .save_to(path)
+      .unwrap_or_else(|e| panic!("Failed to save scene to \"{}\": {}", path, e));
+    info!("Scene saved to \"{}\"", path);
+  }
+
+  scene
+}
+
+/// Loads the bindings file passed in the arguments (or the default bindings) and saves them to
+/// every requested path
+fn load_input_map(args: &Args) -> InputMap {
+  let input_map = match &args.bindings {
+    Some(path) => InputMap::load_from(path)
+      .unwrap_or_else(|e| panic!("Failed to load bindings \"{}\": {}", path, e)),
+    None => InputMap::default(),
+  };
+
+  for path in args.save_bindings.iter() {
+    input_map
+      .save_to(path)
+      .unwrap_or_else(|e| panic!("Failed to save bindings to \"{}\": {}", path, e));
+    info!("Bindings saved to \"{}\"", path);
+  }
+
+  input_map
+}
+
+/// Renders a single frame of the scene from the starting camera position and saves it as an image
+fn render_headless(
+  scene: Scene,
+  output_path: &str,
+  dimensions: [u32; 2],
+  settings: &RenderSettings,
+) -> Result<(), RenderError> {
+  let mut renderer = HeadlessRenderer::initialize(&scene, dimensions, settings)?;
+
+  let mut camera = Camera::new(
+    Point3::new(0.0, 0.0, 0.0),
+    CAMERA_NORMAL_SPEED,
+    0.8,
+    dimensions[0] as f32 / dimensions[1] as f32,
+  );
+  camera.set_reverse_z(settings.reverse_z);
+
+  renderer
+    .render(&camera, &scene)?
+    .save(output_path)
+    .unwrap_or_else(|e| panic!("Failed to save frame to \"{}\": {}", output_path, e));
+
+  info!("Frame saved to \"{}\"", output_path);
+  Ok(())
+}
+
+/// Rendering can't continue after these errors, so the program exits with a message instead of
+/// panicking
+fn exit_with_error(error: RenderError) -> ! {
+  error!("Rendering failed: {}", error);
+  std::process::exit(1);
+}
@@ -1,68 +1,119 @@
 use crate::{
   render::{
+    graphics_pipelines::{GraphicsPipelines, ViewMode},
     renderable_scene::RenderableScene,
+    uniform_data::LightingData,
     vertex_data::{MatrixInstance, Vertex3d},
     vulkano_objects,
-    vulkano_objects::{buffers::Buffers, physical_device::QueueFamilies, Queues},
+    vulkano_objects::{
+      buffers::Buffers, physical_device::QueueFamilies, textures::Textures, Queues,
+    },
+    RenderError,
   },
-  Scene, GENERATE_CUBES,
+  Scene,
 };
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 use vulkano::{
   command_buffer::PrimaryAutoCommandBuffer,
   descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
   device::{Device, Queue},
-  pipeline::{ComputePipeline, GraphicsPipeline, Pipeline},
+  pipeline::{ComputePipeline, Pipeline},
   render_pass::Framebuffer,
 };
 
 pub struct CommandBuffers {
-  pub main: Vec<Arc<PrimaryAutoCommandBuffer>>,
+  /// Recorded for every available view mode, then indexed by frame in flight and by framebuffer,
+  /// as each frame has its own lighting
+  pub main: HashMap<ViewMode, Vec<Vec<Arc<PrimaryAutoCommandBuffer>>>>,
 }
 
 impl CommandBuffers {
   pub fn create(
     device: Arc<Device>,
     queues: &Queues,
-    pipeline: Arc<GraphicsPipeline>,
+    pipelines: &GraphicsPipelines,
     framebuffers: &Vec<Arc<Framebuffer>>,
     buffers: &Buffers<Vertex3d, MatrixInstance>,
-    instance_count_per_model: &Vec<u32>,
-  ) -> Self {
-    let main = vulkano_objects::command_buffers::create_main(
-      device.clone(),
+    descriptor_sets: &DescriptorSets,
+    textures: &Textures,
+  ) -> Result<Self, RenderError> {
+    let main = create_main_per_frame(
+      device,
       queues.graphics.clone(),
-      pipeline,
-      &framebuffers,
-      &buffers,
-      &instance_count_per_model,
-    );
+      pipelines,
+      framebuffers,
+      buffers,
+      descriptor_sets,
+      textures,
+    )?;
 
-    Self { main }
+    Ok(Self { main })
   }
 
   pub fn recreate_main(
     &mut self,
     device: Arc<Device>,
     graphics_queue: Arc<Queue>,
-    pipeline: Arc<GraphicsPipeline>,
+    pipelines: &GraphicsPipelines,
     framebuffers: &Vec<Arc<Framebuffer>>,
     buffers: &Buffers<Vertex3d, MatrixInstance>,
-    instance_count_per_model: &Vec<u32>,
-  ) {
-    self.main = vulkano_objects::command_buffers::create_main(
-      device.clone(),
+    descriptor_sets: &DescriptorSets,
+    textures: &Textures,
+  ) -> Result<(), RenderError> {
+    self.main = create_main_per_frame(
+      device,
       graphics_queue,
-      pipeline,
-      &framebuffers,
-      &buffers,
-      &instance_count_per_model,
-    );
+      pipelines,
+      framebuffers,
+      buffers,
+      descriptor_sets,
+      textures,
+    )?;
+    Ok(())
   }
 }
 
+fn create_main_per_frame(
+  device: Arc<Device>,
+  graphics_queue: Arc<Queue>,
+  pipelines: &GraphicsPipelines,
+  framebuffers: &Vec<Arc<Framebuffer>>,
+  buffers: &Buffers<Vertex3d, MatrixInstance>,
+  descriptor_sets: &DescriptorSets,
+  textures: &Textures,
+) -> Result<HashMap<ViewMode, Vec<Vec<Arc<PrimaryAutoCommandBuffer>>>>, RenderError> {
+  pipelines
+    .available_view_modes()
+    .into_iter()
+    .map(|view_mode| {
+      let per_frame = descriptor_sets
+        .lighting
+        .iter()
+        .enumerate()
+        .map(|(frame_i, lighting)| {
+          vulkano_objects::command_buffers::create_main(
+            device.clone(),
+            graphics_queue.clone(),
+            pipelines,
+            view_mode,
+            framebuffers,
+            buffers,
+            frame_i,
+            lighting.clone(),
+            textures,
+          )
+        })
+        .collect::<Result<_, _>>()?;
+      Ok((view_mode, per_frame))
+    })
+    .collect()
+}
+
+/// Descriptor sets for each frame in flight
 pub struct DescriptorSets {
   pub instance: Vec<Arc<PersistentDescriptorSet>>,
+  /// Used in the main command buffers
+  pub lighting: Vec<Arc<PersistentDescriptorSet>>,
 }
 
 /// responsible for managing data between existing buffers and command_buffers
@@ -70,7 +121,9 @@ pub struct BufferContainer {
   command_buffers: CommandBuffers,
   buffers: Buffers<Vertex3d, MatrixInstance>,
   descriptor_sets: DescriptorSets,
-  instance_count_per_model_cache: Vec<u32>,
+  textures: Textures,
+  /// Used to recalculate model data when the number of objects changes
+  bounding_radii: Vec<f32>,
 }
 
 impl BufferContainer {
@@ -78,91 +131,155 @@ impl BufferContainer {
     device: Arc<Device>,
     queue_families: &QueueFamilies,
     queues: &Queues,
-    graphics_pipeline: Arc<GraphicsPipeline>,
+    graphics_pipelines: &GraphicsPipelines,
     compute_pipeline: Arc<ComputePipeline>,
     framebuffers: &Vec<Arc<Framebuffer>>,
+    frames_in_flight: usize,
     scene: &Scene,
-  ) -> Self {
-    let max_instances = if let Some(value) = GENERATE_CUBES {
-      (value * value * value) + 256
-    } else {
-      256
-    };
+  ) -> Result<Self, RenderError> {
+    // some space is left for objects spawned later, buffers grow when they get full anyway
+    let max_instances = scene.total_object_count + 256;
+
+    let models = RenderableScene::get_models(scene)?;
 
-    // uniform buffer count is assigned to the number of image, in this case the number of framebuffers
+    // buffers written by the cpu are separate for each frame in flight, so that updating them
+    // doesn't need to wait for frames that are still running
     let buffers = Buffers::<Vertex3d, MatrixInstance>::initialize(
       device.clone(),
       queue_families,
       queues.transfers.clone(),
-      framebuffers.len(),
-      &RenderableScene::get_models(),
+      frames_in_flight,
+      &models,
       max_instances,
-    );
+    )?;
+
+    let textures = Textures::load(
+      device.clone(),
+      queues.graphics.clone(),
+      graphics_pipelines
+        .textured
+        .layout()
+        .set_layouts()
+        .get(1)
+        .expect("the textured pipeline has a texture set")
+        .clone(),
+      &models,
+    )?;
 
-    let layout = compute_pipeline.layout().set_layouts().get(0).unwrap();
+    // set 0 is the same in every graphics pipeline
+    let graphics_layout = graphics_pipelines
+      .lit
+      .layout()
+      .set_layouts()
+      .get(0)
+      .expect("graphics pipelines have a lighting set");
     let descriptor_sets = DescriptorSets {
-      instance: (0..3)
+      instance: create_instance_descriptor_sets(&compute_pipeline, &buffers, frames_in_flight)?,
+      lighting: (0..frames_in_flight)
         .map(|i| {
           PersistentDescriptorSet::new(
-            layout.clone(),
+            graphics_layout.clone(),
             [
-              WriteDescriptorSet::buffer(0, buffers.get_instance_source_model(i).clone()),
-              WriteDescriptorSet::buffer(1, buffers.get_main().instance.clone()),
+              WriteDescriptorSet::buffer(0, buffers.get_lighting(i)),
+              WriteDescriptorSet::buffer(1, buffers.get_main().frames[i].frame_data.clone()),
             ],
           )
-          .unwrap()
         })
-        .collect(),
+        .collect::<Result<_, _>>()?,
     };
 
-    let instance_count_per_model: Vec<u32> = RenderableScene::instance_count_per_model(scene)
-      .drain(0..)
-      .map(|n| n as u32)
-      .collect();
-
     let command_buffers = CommandBuffers::create(
       device,
       queues,
-      graphics_pipeline,
+      graphics_pipelines,
       framebuffers,
       &buffers,
-      &instance_count_per_model,
-    );
+      &descriptor_sets,
+      &textures,
+    )?;
 
-    Self {
+    Ok(Self {
       buffers,
       descriptor_sets,
+      textures,
       command_buffers,
-      instance_count_per_model_cache: instance_count_per_model,
-    }
+      bounding_radii: RenderableScene::get_bounding_radii(&models),
+    })
   }
 
   pub fn handle_window_resize(
     &mut self,
     device: Arc<Device>,
     graphics_queue: Arc<Queue>,
-    pipeline: Arc<GraphicsPipeline>,
+    pipelines: &GraphicsPipelines,
     framebuffers: &Vec<Arc<Framebuffer>>,
-  ) {
+  ) -> Result<(), RenderError> {
     self.command_buffers.recreate_main(
       device,
       graphics_queue,
-      pipeline,
+      pipelines,
+      framebuffers,
+      &self.buffers,
+      &self.descriptor_sets,
+      &self.textures,
+    )
+  }
+
+  /// Makes sure that instance buffers can hold every object of the scene. When they get
+  /// reallocated, descriptor sets and command buffers that use them get recreated.
+  pub fn reserve_instances(
+    &mut self,
+    device: Arc<Device>,
+    queues: &Queues,
+    graphics_pipelines: &GraphicsPipelines,
+    compute_pipeline: &Arc<ComputePipeline>,
+    framebuffers: &Vec<Arc<Framebuffer>>,
+    instance_count: usize,
+  ) -> Result<(), RenderError> {
+    if !self
+      .buffers
+      .reserve_instances(device.clone(), queues, instance_count)?
+    {
+      return Ok(());
+    }
+
+    self.descriptor_sets.instance = create_instance_descriptor_sets(
+      compute_pipeline,
+      &self.buffers,
+      self.descriptor_sets.instance.len(),
+    )?;
+    self.command_buffers.recreate_main(
+      device,
+      queues.graphics.clone(),
+      graphics_pipelines,
       framebuffers,
       &self.buffers,
-      &self.instance_count_per_model_cache,
+      &self.descriptor_sets,
+      &self.textures,
     )
   }
 
-  pub fn update_buffer_models(&mut self, buffer_i: usize, scene: &Scene) {
+  /// Uploads model matrices, materials and lights of all objects, together with the instance
+  /// ranges of each model. Main command buffers only read these through draw commands, so
+  /// they stay valid when the number of objects changes.
+  pub fn update_buffer_models(
+    &mut self,
+    buffer_i: usize,
+    scene: &Scene,
+  ) -> Result<(), RenderError> {
     self.buffers.update_instance_source_models(
       buffer_i,
-      RenderableScene::into_matrices(scene)
-        .map(|model| MatrixInstance {
-          matrix: model.into(),
-        })
+      RenderableScene::into_instances(scene)
+        .map(|(model, material)| MatrixInstance::new(model, material))
         .collect(),
-    )
+    )?;
+    self.buffers.update_model_data(
+      buffer_i,
+      RenderableScene::get_model_data(scene, &self.bounding_radii),
+    )?;
+    self
+      .buffers
+      .update_lighting(buffer_i, LightingData::from(&scene.lighting))
   }
 
   pub fn command_buffers(&self) -> &CommandBuffers {
@@ -172,4 +289,40 @@ impl BufferContainer {
   pub fn descriptor_sets(&self) -> &DescriptorSets {
     &self.descriptor_sets
   }
+
+  pub fn buffers(&self) -> &Buffers<Vertex3d, MatrixInstance> {
+    &self.buffers
+  }
+
+  pub fn model_count(&self) -> usize {
+    self.buffers.get_main().model_lengths.len()
+  }
+}
+
+/// Creates the instance compute descriptor sets, one for each frame in flight
+fn create_instance_descriptor_sets(
+  compute_pipeline: &Arc<ComputePipeline>,
+  buffers: &Buffers<Vertex3d, MatrixInstance>,
+  count: usize,
+) -> Result<Vec<Arc<PersistentDescriptorSet>>, RenderError> {
+  let layout = compute_pipeline
+    .layout()
+    .set_layouts()
+    .get(0)
+    .expect("the instance compute pipeline has a descriptor set");
+  (0..count)
+    .map(|i| {
+      PersistentDescriptorSet::new(
+        layout.clone(),
+        [
+          WriteDescriptorSet::buffer(0, buffers.get_instance_source_model(i).clone()),
+          WriteDescriptorSet::buffer(1, buffers.get_main().frames[i].instance.clone()),
+          WriteDescriptorSet::buffer(2, buffers.get_main().frames[i].frame_data.clone()),
+          WriteDescriptorSet::buffer(3, buffers.get_model_data(i)),
+          WriteDescriptorSet::buffer(4, buffers.get_main().frames[i].draw_commands.clone()),
+        ],
+      )
+    })
+    .collect::<Result<_, _>>()
+    .map_err(RenderError::from)
 }
@@ -2,9 +2,14 @@ use std::f32::consts::PI;
 
 use cgmath::{InnerSpace, Matrix4, PerspectiveFov, Point3, Rad, Vector3};
 
-use crate::{app::Mouse, Keys, Pressed};
+use crate::{
+  app::Mouse,
+  input::{Action, InputState},
+};
 
 const HALF_PI: f32 = PI / 2.0;
+const NEAR: f32 = 0.1;
+const FAR: f32 = 1000.0;
 
 /// Camera with dynamic up / down directions.
 pub struct Camera {
@@ -20,6 +25,8 @@ pub struct Camera {
   pub sensitivity: f32,
   pub fov: f32,
   aspect_ratio: f32,
+  /// Uses a reverse-Z projection with an infinite far plane, see [`Camera::set_reverse_z`]
+  reverse_z: bool,
   /// Cached because it's not frequently updated
   projection_matrix: Matrix4<f32>,
 }
@@ -36,7 +43,8 @@ impl Camera {
       sensitivity: 0.003,
       fov,
       aspect_ratio,
-      projection_matrix: get_projection_matrix(fov, aspect_ratio),
+      reverse_z: false,
+      projection_matrix: get_projection_matrix(fov, aspect_ratio, false),
     }
   }
 
@@ -54,7 +62,15 @@ impl Camera {
 
   pub fn set_aspect_ratio(&mut self, value: f32) {
     self.aspect_ratio = value;
-    self.projection_matrix = get_projection_matrix(self.fov, value);
+    self.projection_matrix = get_projection_matrix(self.fov, value, self.reverse_z);
+  }
+
+  /// Reverse-Z maps the near plane to depth 1 and infinity to 0, which spreads float depth
+  /// precision evenly with distance. Pipelines have to use a `Greater` depth test and clear
+  /// depth to 0 in order to work with it.
+  pub fn set_reverse_z(&mut self, value: bool) {
+    self.reverse_z = value;
+    self.projection_matrix = get_projection_matrix(self.fov, self.aspect_ratio, value);
   }
 
   pub fn handle_zoom(&mut self, amount: f32) {
@@ -65,12 +81,20 @@ impl Camera {
       self.fov = PI / 15.0;
     }
 
-    self.projection_matrix = get_projection_matrix(self.fov, self.aspect_ratio);
+    self.projection_matrix = get_projection_matrix(self.fov, self.aspect_ratio, self.reverse_z);
   }
 
   pub fn handle_mouse_movement(&mut self, mouse: &Mouse) {
-    self.yaw += mouse.delta_x * self.sensitivity;
-    self.pitch += mouse.delta_y * self.sensitivity;
+    self.rotate(
+      mouse.delta_x * self.sensitivity,
+      mouse.delta_y * self.sensitivity,
+    );
+  }
+
+  /// Adds to yaw and pitch in radians, a positive pitch looks down
+  pub fn rotate(&mut self, yaw: f32, pitch: f32) {
+    self.yaw += yaw;
+    self.pitch += pitch;
 
     if self.pitch > HALF_PI - 0.1 {
       self.pitch = HALF_PI - 0.1;
@@ -87,35 +111,50 @@ impl Camera {
     };
   }
 
-  pub fn handle_keys(&mut self, keys: &Keys, delta_time: &std::time::Duration) {
+  pub fn handle_input(&mut self, input: &InputState, delta_time: &std::time::Duration) {
+    let axis = |positive: Action, negative: Action| {
+      input.is_active(positive) as i32 as f32 - input.is_active(negative) as i32 as f32
+    };
+    let movement = Vector3::new(
+      axis(Action::MoveRight, Action::MoveLeft),
+      axis(Action::MoveUp, Action::MoveDown),
+      axis(Action::MoveForward, Action::MoveBackward),
+    );
+
+    self.fly(movement, delta_time);
+  }
+
+  /// Moves relative to where the camera is looking, with each component of `movement` going
+  /// from -1 to 1 at full speed. X goes right, Y goes up and Z goes forward.
+  pub fn fly(&mut self, movement: Vector3<f32>, delta_time: &std::time::Duration) {
     let delta_speed = self.speed * delta_time.as_secs_f32();
-    if keys.w == Pressed {
-      self.position += self.front * delta_speed;
-    }
-    if keys.a == Pressed {
-      self.position -= self.front.cross(self.up).normalize() * delta_speed;
-    }
-    if keys.s == Pressed {
-      self.position -= self.front * delta_speed;
-    }
-    if keys.d == Pressed {
-      self.position += self.front.cross(self.up).normalize() * delta_speed;
-    }
-    if keys.space == Pressed {
-      self.position -= self.up * delta_speed;
-    }
-    if keys.l_ctrl == Pressed {
-      self.position += self.up * delta_speed;
-    }
+    let right = self.front.cross(self.up).normalize();
+
+    // up is negative Y in world space
+    self.position +=
+      (self.front * movement.z + right * movement.x - self.up * movement.y) * delta_speed;
   }
 }
 
-fn get_projection_matrix(fov: f32, aspect_ratio: f32) -> Matrix4<f32> {
+fn get_projection_matrix(fov: f32, aspect_ratio: f32, reverse_z: bool) -> Matrix4<f32> {
+  if reverse_z {
+    // depth is near / distance, so it never reaches 0 (cgmath takes the columns in order)
+    let f = 1.0 / (fov / 2.0).tan();
+    #[rustfmt::skip]
+    let matrix = Matrix4::new(
+      f / aspect_ratio, 0.0, 0.0, 0.0,
+      0.0, f, 0.0, 0.0,
+      0.0, 0.0, 0.0, -1.0,
+      0.0, 0.0, NEAR, 0.0,
+    );
+    return matrix;
+  }
+
   PerspectiveFov {
     fovy: Rad(fov),
     aspect: aspect_ratio,
-    far: 1000.0,
-    near: 0.1,
+    far: FAR,
+    near: NEAR,
   }
   .into()
 }
@@ -0,0 +1,153 @@
+/// Fences and outdated buffers of the frames in flight, which go around in a ring.
+///
+/// Kept apart from the renderer so that the order in which frames wait and update their buffers
+/// doesn't depend on a device. `F` is the fence type, which only gets stored and handed back.
+pub struct FrameRing<F> {
+  /// One for each frame in flight, `None` until the frame gets flushed
+  fences: Vec<Option<F>>,
+  previous_frame_i: usize,
+  /// Whether the buffers written by the cpu are outdated, one for each frame in flight
+  update_buffer_models: Vec<bool>,
+}
+
+impl<F> FrameRing<F> {
+  pub fn new(frames_in_flight: usize) -> Self {
+    assert!(
+      frames_in_flight > 0,
+      "There must be at least one frame in flight"
+    );
+
+    Self {
+      fences: (0..frames_in_flight).map(|_| None).collect(),
+      // so that the first frame is 0
+      previous_frame_i: frames_in_flight - 1,
+      update_buffer_models: vec![false; frames_in_flight],
+    }
+  }
+
+  /// Frame in flight that gets drawn next
+  pub fn next_frame_i(&self) -> usize {
+    (self.previous_frame_i + 1) % self.fences.len()
+  }
+
+  /// Fence that has to be waited before drawing the next frame.
+  ///
+  /// This is the only fence the cpu waits for. It belongs to the oldest flushed frame, so waiting
+  /// for it doesn't wait upon the execution of the other frames, and it unlocks every buffer that
+  /// only this frame uses.
+  pub fn fence_to_wait(&self) -> Option<&F> {
+    self.fences[self.next_frame_i()].as_ref()
+  }
+
+  /// Fence of the last flushed frame, which the next one has to start after
+  pub fn previous_fence(&self) -> Option<&F> {
+    self.fences[self.previous_frame_i].as_ref()
+  }
+
+  /// Marks the buffers of every frame as outdated
+  pub fn invalidate_buffers(&mut self) {
+    self.update_buffer_models.fill(true);
+  }
+
+  /// Returns whether the buffers of the next frame are outdated, marking them as updated.
+  ///
+  /// They are separate for each frame in flight, so they can be written right after waiting
+  /// for [`Self::fence_to_wait`], even if other frames still use their own.
+  pub fn take_buffer_update(&mut self) -> bool {
+    let frame_i = self.next_frame_i();
+    std::mem::replace(&mut self.update_buffer_models[frame_i], false)
+  }
+
+  /// Stores the fence of the next frame, which then becomes the previous one
+  pub fn finish_frame(&mut self, fence: Option<F>) {
+    let frame_i = self.next_frame_i();
+    self.fences[frame_i] = fence;
+    self.previous_frame_i = frame_i;
+  }
+
+  /// Starts again from the first frame with every buffer outdated,
+  /// returning the fences that were still stored
+  pub fn reset(&mut self) -> Vec<F> {
+    let fences = self.fences.iter_mut().filter_map(Option::take).collect();
+    self.previous_frame_i = self.fences.len() - 1;
+    self.invalidate_buffers();
+    fences
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Flushes `count` frames, using the number of the flush as fence
+  fn flush_frames(ring: &mut FrameRing<u32>, count: u32) {
+    for fence in 0..count {
+      ring.finish_frame(Some(fence));
+    }
+  }
+
+  #[test]
+  fn first_frames_wait_for_nothing() {
+    let mut ring = FrameRing::<u32>::new(3);
+    for frame_i in 0..3 {
+      assert_eq!(ring.next_frame_i(), frame_i);
+      assert_eq!(ring.fence_to_wait(), None);
+      ring.finish_frame(Some(frame_i as u32));
+    }
+  }
+
+  #[test]
+  fn waits_only_for_own_fence() {
+    for frames_in_flight in 2..5 {
+      let mut ring = FrameRing::<u32>::new(frames_in_flight);
+      flush_frames(&mut ring, frames_in_flight as u32);
+
+      for fence in frames_in_flight as u32..20 {
+        let frame_i = ring.next_frame_i();
+        let own_fence = ring.fences[frame_i];
+        let previous_fence = ring.previous_fence().copied();
+
+        assert_eq!(ring.fence_to_wait().copied(), own_fence);
+        assert_ne!(ring.fence_to_wait().copied(), previous_fence);
+        assert_eq!(previous_fence, Some(fence - 1));
+
+        ring.finish_frame(Some(fence));
+      }
+    }
+  }
+
+  #[test]
+  fn single_frame_waits_for_itself() {
+    let mut ring = FrameRing::<u32>::new(1);
+    ring.finish_frame(Some(7));
+
+    assert_eq!(ring.next_frame_i(), 0);
+    assert_eq!(ring.fence_to_wait(), Some(&7));
+  }
+
+  #[test]
+  fn updates_only_next_frame_buffers() {
+    let mut ring = FrameRing::<u32>::new(3);
+    ring.invalidate_buffers();
+
+    assert!(ring.take_buffer_update());
+    assert!(!ring.take_buffer_update());
+    assert_eq!(ring.update_buffer_models, [false, true, true]);
+
+    ring.finish_frame(Some(0));
+    assert!(ring.take_buffer_update());
+    assert_eq!(ring.update_buffer_models, [false, false, true]);
+  }
+
+  #[test]
+  fn reset_returns_fences() {
+    let mut ring = FrameRing::<u32>::new(3);
+    flush_frames(&mut ring, 2);
+
+    assert_eq!(ring.reset(), vec![0, 1]);
+    assert_eq!(ring.next_frame_i(), 0);
+    assert_eq!(ring.fence_to_wait(), None);
+    assert_eq!(ring.previous_fence(), None);
+    assert_eq!(ring.update_buffer_models, [true, true, true]);
+  }
+}
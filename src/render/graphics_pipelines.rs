@@ -0,0 +1,197 @@
+use crate::render::{
+  shaders::{debug, lit, textured},
+  vulkano_objects,
+};
+use std::sync::Arc;
+use vulkano::{
+  device::Device,
+  pipeline::{graphics::viewport::Viewport, GraphicsPipeline, Pipeline},
+  render_pass::RenderPass,
+  shader::ShaderModule,
+};
+
+/// Ways of drawing the scene that can be switched while running
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ViewMode {
+  Shaded,
+  /// Only available with the `fill_mode_non_solid` device feature
+  Wireframe,
+  Normals,
+  /// Values in the depth buffer, brighter is closer
+  Depth,
+  /// Each object gets a different color, which stays the same while the camera moves
+  InstanceIds,
+}
+
+impl ViewMode {
+  pub const ALL: [ViewMode; 5] = [
+    ViewMode::Shaded,
+    ViewMode::Wireframe,
+    ViewMode::Normals,
+    ViewMode::Depth,
+    ViewMode::InstanceIds,
+  ];
+
+  /// Value of the `mode` specialization constant in the debug fragment shader
+  fn debug_shader_mode(&self) -> Option<u32> {
+    match self {
+      ViewMode::Normals => Some(0),
+      ViewMode::Depth => Some(1),
+      ViewMode::InstanceIds => Some(2),
+      ViewMode::Shaded | ViewMode::Wireframe => None,
+    }
+  }
+}
+
+/// Pipelines used in the main command buffers.
+///
+/// Models without a texture get drawn with `lit` and the others with `textured`.
+/// Both use the same descriptor set 0 (lighting), while `textured` also uses set 1 for the image.
+/// Other view modes have their own pipelines with the same set 0, see [`GraphicsPipelines::get`].
+pub struct GraphicsPipelines {
+  pub lit: Arc<GraphicsPipeline>,
+  pub textured: Arc<GraphicsPipeline>,
+  /// Same as `lit` and `textured`, but only draw edges
+  wireframe: Option<[Arc<GraphicsPipeline>; 2]>,
+  /// Normals, depth and instance ids, which don't use textures
+  debug: [Arc<GraphicsPipeline>; 3],
+  /// Depth test passes for bigger values, so depth attachments have to be cleared to 0
+  pub reverse_z: bool,
+  // kept in order to recreate the pipelines when the viewport changes
+  shaders: [Arc<ShaderModule>; 6],
+}
+
+impl GraphicsPipelines {
+  pub fn new(
+    device: Arc<Device>,
+    render_pass: Arc<RenderPass>,
+    viewport: Viewport,
+    reverse_z: bool,
+  ) -> Self {
+    let load_error = "failed to create shader module";
+    let shaders = [
+      lit::vs::load(device.clone()).expect(load_error),
+      lit::fs::load(device.clone()).expect(load_error),
+      textured::vs::load(device.clone()).expect(load_error),
+      textured::fs::load(device.clone()).expect(load_error),
+      debug::vs::load(device.clone()).expect(load_error),
+      debug::fs::load(device.clone()).expect(load_error),
+    ];
+
+    let (lit, textured, wireframe, debug) =
+      Self::create(device, &shaders, render_pass, viewport, reverse_z);
+    Self {
+      lit,
+      textured,
+      wireframe,
+      debug,
+      reverse_z,
+      shaders,
+    }
+  }
+
+  pub fn recreate(
+    &mut self,
+    device: Arc<Device>,
+    render_pass: Arc<RenderPass>,
+    viewport: Viewport,
+  ) {
+    let (lit, textured, wireframe, debug) =
+      Self::create(device, &self.shaders, render_pass, viewport, self.reverse_z);
+    self.lit = lit;
+    self.textured = textured;
+    self.wireframe = wireframe;
+    self.debug = debug;
+  }
+
+  /// Returns the pipelines that draw models without and with a texture in this view mode,
+  /// or None if the device doesn't support it.
+  ///
+  /// Only pipelines with more than one descriptor set use textures.
+  pub fn get(&self, view_mode: ViewMode) -> Option<[Arc<GraphicsPipeline>; 2]> {
+    if let Some(mode) = view_mode.debug_shader_mode() {
+      let pipeline = self.debug[mode as usize].clone();
+      return Some([pipeline.clone(), pipeline]);
+    }
+
+    match view_mode {
+      ViewMode::Wireframe => self.wireframe.clone(),
+      _ => Some([self.lit.clone(), self.textured.clone()]),
+    }
+  }
+
+  /// View modes that can be used with this device
+  pub fn available_view_modes(&self) -> Vec<ViewMode> {
+    ViewMode::ALL
+      .into_iter()
+      .filter(|&mode| self.get(mode).is_some())
+      .collect()
+  }
+
+  #[allow(clippy::type_complexity)]
+  fn create(
+    device: Arc<Device>,
+    [lit_vs, lit_fs, textured_vs, textured_fs, debug_vs, debug_fs]: &[Arc<ShaderModule>; 6],
+    render_pass: Arc<RenderPass>,
+    viewport: Viewport,
+    reverse_z: bool,
+  ) -> (
+    Arc<GraphicsPipeline>,
+    Arc<GraphicsPipeline>,
+    Option<[Arc<GraphicsPipeline>; 2]>,
+    [Arc<GraphicsPipeline>; 3],
+  ) {
+    let lit = vulkano_objects::pipeline::create_graphics(
+      device.clone(),
+      lit_vs.clone(),
+      lit_fs.clone(),
+      render_pass.clone(),
+      viewport.clone(),
+      reverse_z,
+    );
+    let textured = vulkano_objects::pipeline::create_graphics(
+      device.clone(),
+      textured_vs.clone(),
+      textured_fs.clone(),
+      render_pass.clone(),
+      viewport.clone(),
+      reverse_z,
+    );
+
+    let wireframe = if device.enabled_features().fill_mode_non_solid {
+      Some(
+        [(lit_vs, lit_fs), (textured_vs, textured_fs)].map(|(vs, fs)| {
+          vulkano_objects::pipeline::create_wireframe(
+            device.clone(),
+            vs.clone(),
+            fs.clone(),
+            render_pass.clone(),
+            viewport.clone(),
+            reverse_z,
+          )
+        }),
+      )
+    } else {
+      None
+    };
+
+    let debug = [0, 1, 2].map(|mode| {
+      vulkano_objects::pipeline::create_debug(
+        device.clone(),
+        debug_vs.clone(),
+        debug_fs.clone(),
+        render_pass.clone(),
+        viewport.clone(),
+        reverse_z,
+        mode,
+      )
+    });
+
+    (lit, textured, wireframe, debug)
+  }
+}
+
+/// Returns true if models drawn with this pipeline need their texture bound in set 1
+pub fn uses_textures(pipeline: &GraphicsPipeline) -> bool {
+  pipeline.layout().set_layouts().len() > 1
+}
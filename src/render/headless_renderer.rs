@@ -0,0 +1,271 @@
+use crate::{
+  render::{
+    buffer_container::BufferContainer,
+    graphics_pipelines::{GraphicsPipelines, ViewMode},
+    offscreen_container::OffscreenContainer,
+    shaders::compute,
+    vertex_data::{MatrixInstance, Vertex3d},
+    vulkano_objects,
+    vulkano_objects::Queues,
+    Camera, RenderError, RenderSettings,
+  },
+  Scene,
+};
+use image::{ImageBuffer, Rgba};
+use log::info;
+use std::sync::Arc;
+use vulkano::{
+  device::{Device, DeviceCreateInfo, DeviceExtensions},
+  instance::{debug::DebugCallback, Instance},
+  pipeline::{graphics::viewport::Viewport, ComputePipeline},
+  sync::{self, GpuFuture},
+};
+
+/// Renders the scene into an offscreen image instead of a window.
+///
+/// Doesn't need a display or a surface, so it can be used with software drivers
+/// (for example lavapipe) in order to compare frames with previously saved ones.
+/// Every call to [`HeadlessRenderer::render`] waits for the gpu to finish.
+pub struct HeadlessRenderer {
+  _instance: Arc<Instance>,
+  _debug_callback: Option<DebugCallback>,
+  device: Arc<Device>,
+  queues: Queues,
+  offscreen_container: OffscreenContainer,
+  graphics_pipelines: GraphicsPipelines,
+  compute_pipeline: Arc<ComputePipeline>,
+  buffer_container: BufferContainer,
+}
+
+impl HeadlessRenderer {
+  /// Only uses the settings that don't depend on a window
+  pub fn initialize(
+    scene: &Scene,
+    dimensions: [u32; 2],
+    settings: &RenderSettings,
+  ) -> Result<Self, RenderError> {
+    let (instance, debug_callback) =
+      vulkano_objects::instance::create_headless(settings.validation)?;
+
+    let device_extensions = DeviceExtensions {
+      khr_storage_buffer_storage_class: true,
+      ..DeviceExtensions::none()
+    };
+
+    let (physical_device, queue_families) = vulkano_objects::physical_device::select_headless(
+      &instance,
+      &device_extensions,
+      settings.device.as_ref(),
+    )?;
+
+    let (device, queues) = Device::new(
+      physical_device,
+      DeviceCreateInfo {
+        queue_create_infos: queue_families.get_queue_create_info(),
+        enabled_extensions: physical_device
+          .required_extensions()
+          .union(&device_extensions),
+        enabled_features: vulkano_objects::physical_device::get_enabled_features(physical_device),
+        ..Default::default()
+      },
+    )?;
+
+    let queues = queue_families.get_queues(queues);
+    info!("Queues: {:?}", queues.mapping);
+
+    let offscreen_container = OffscreenContainer::new(
+      device.clone(),
+      queues.graphics.clone(),
+      dimensions,
+      vulkano_objects::render_pass::choose_sample_count(physical_device, settings.msaa_samples),
+    )?;
+
+    let viewport = Viewport {
+      origin: [0.0, 0.0],
+      dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+      depth_range: 0.0..1.0,
+    };
+
+    let graphics_pipelines = GraphicsPipelines::new(
+      device.clone(),
+      offscreen_container.get_render_pass(),
+      viewport,
+      settings.reverse_z,
+    );
+
+    let compute_pipeline = vulkano_objects::pipeline::create_compute(
+      device.clone(),
+      compute::instance::load(device.clone())?,
+    );
+
+    let buffer_container = BufferContainer::new(
+      device.clone(),
+      &queue_families,
+      &queues,
+      &graphics_pipelines,
+      compute_pipeline.clone(),
+      offscreen_container.get_framebuffers(),
+      1,
+      scene,
+    )?;
+
+    Ok(Self {
+      _instance: instance,
+      _debug_callback: debug_callback,
+      device,
+      queues,
+      offscreen_container,
+      graphics_pipelines,
+      compute_pipeline,
+      buffer_container,
+    })
+  }
+
+  /// Draws a single frame and returns its contents
+  pub fn render(
+    &mut self,
+    camera: &Camera,
+    scene: &Scene,
+  ) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>, RenderError> {
+    // there is only one framebuffer and frame in flight, so every resource has index 0
+    self.buffer_container.reserve_instances(
+      self.device.clone(),
+      &self.queues,
+      &self.graphics_pipelines,
+      &self.compute_pipeline,
+      self.offscreen_container.get_framebuffers(),
+      scene.total_object_count,
+    )?;
+    self.buffer_container.update_buffer_models(0, scene)?;
+
+    let instance_compute_command_buffer =
+      vulkano_objects::command_buffers::create_instance_compute::<Vertex3d, MatrixInstance, _>(
+        self.device.clone(),
+        self.queues.compute.clone(),
+        self.compute_pipeline.clone(),
+        self.buffer_container.buffers(),
+        0,
+        self.buffer_container.descriptor_sets().instance[0].clone(),
+        compute::instance::ty::PushConstantData {
+          projection_view: camera.get_projection_view().into(),
+          camera_position: camera.position.to_homogeneous().into(),
+          counts: [
+            scene.total_object_count as u32,
+            self.buffer_container.model_count() as u32,
+            0,
+            0,
+          ],
+        },
+        scene.total_object_count,
+      )?;
+
+    sync::now(self.device.clone())
+      .then_execute(self.queues.compute.clone(), instance_compute_command_buffer)?
+      .then_signal_semaphore()
+      .then_execute(
+        self.queues.graphics.clone(),
+        self.buffer_container.command_buffers().main[&ViewMode::Shaded][0][0].clone(),
+      )?
+      .then_execute(
+        self.queues.graphics.clone(),
+        self.offscreen_container.get_copy_command_buffer(),
+      )?
+      .then_signal_fence_and_flush()?
+      .wait(None)?;
+
+    let [width, height] = self.offscreen_container.dimensions();
+    let output_buffer = self.offscreen_container.get_output_buffer();
+    let content = output_buffer.read()?;
+
+    ImageBuffer::from_raw(width, height, content.to_vec()).ok_or(RenderError::OutputSize {
+      dimensions: [width, height],
+      len: content.len(),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    game_objects::{Cube, SceneObject},
+    CAMERA_NORMAL_SPEED,
+  };
+  use cgmath::Point3;
+  use std::path::Path;
+
+  const GOLDEN_IMAGE: &str = "tests/golden/headless_cubes.png";
+  const DIMENSIONS: [u32; 2] = [320, 240];
+  /// Largest difference of a channel before a pixel counts as different,
+  /// drivers are allowed to round a bit differently
+  const CHANNEL_TOLERANCE: u8 = 8;
+  /// Fraction of pixels that can differ, mostly along edges
+  const MAX_DIFFERENT_PIXELS: f32 = 0.005;
+
+  /// Default scene with the random cubes replaced by a few fixed ones
+  fn golden_scene() -> Scene {
+    let mut scene = Scene::load();
+    for handle in scene.cube_handles().to_vec() {
+      scene.despawn(handle);
+    }
+    for position in [
+      Point3::new(0.0, 0.0, -4.0),
+      Point3::new(-2.0, 1.0, -6.0),
+      Point3::new(2.5, -1.0, -5.0),
+    ] {
+      scene.spawn(SceneObject::Cube(Cube::new(position)));
+    }
+    scene
+  }
+
+  /// Needs a Vulkan driver, run with `cargo test -- --ignored`.
+  /// After an intended change in rendering, `UPDATE_GOLDEN=1 cargo test -- --ignored` replaces the
+  /// reference image, which should be looked at before committing it.
+  #[test]
+  #[ignore]
+  fn renders_golden_image() {
+    let scene = golden_scene();
+    let settings = RenderSettings::default();
+    let mut renderer = HeadlessRenderer::initialize(&scene, DIMENSIONS, &settings).unwrap();
+
+    // same camera as `--headless`
+    let camera = Camera::new(
+      Point3::new(0.0, 0.0, 0.0),
+      CAMERA_NORMAL_SPEED,
+      0.8,
+      DIMENSIONS[0] as f32 / DIMENSIONS[1] as f32,
+    );
+    let frame = renderer.render(&camera, &scene).unwrap();
+
+    let golden_path = Path::new(env!("CARGO_MANIFEST_DIR")).join(GOLDEN_IMAGE);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+      std::fs::create_dir_all(golden_path.parent().unwrap()).unwrap();
+      frame.save(&golden_path).unwrap();
+      return;
+    }
+
+    let golden = image::open(&golden_path)
+      .unwrap_or_else(|e| panic!("Failed to open {}: {}", golden_path.display(), e))
+      .into_rgba8();
+    assert_eq!(golden.dimensions(), frame.dimensions());
+
+    let different = frame
+      .pixels()
+      .zip(golden.pixels())
+      .filter(|(a, b)| {
+        a.0
+          .iter()
+          .zip(b.0.iter())
+          .any(|(a, b)| a.abs_diff(*b) > CHANNEL_TOLERANCE)
+      })
+      .count();
+    let allowed = (MAX_DIFFERENT_PIXELS * (DIMENSIONS[0] * DIMENSIONS[1]) as f32) as usize;
+    assert!(
+      different <= allowed,
+      "{} pixels differ from {}, at most {} can",
+      different,
+      GOLDEN_IMAGE,
+      allowed
+    );
+  }
+}
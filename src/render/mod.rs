@@ -3,14 +3,33 @@
 
 mod buffer_container;
 mod camera;
+mod frame_ring;
+mod graphics_pipelines;
+mod headless_renderer;
 mod models;
+mod offscreen_container;
+mod render_error;
 mod render_loop;
+mod render_settings;
 mod renderable_scene;
 mod renderer;
 mod shaders;
 mod swapchain_container;
+mod uniform_data;
 mod vertex_data;
 mod vulkano_objects;
 
 pub use camera::Camera;
+pub use headless_renderer::HeadlessRenderer;
+pub use models::{ModelLoadError, ObjModel};
+pub use render_error::RenderError;
 pub use render_loop::RenderLoop;
+pub use render_settings::RenderSettings;
+pub use vulkano_objects::DeviceChoice;
+
+/// Prints every device that can be chosen with `--device` with its queue families
+pub fn print_devices() -> Result<(), RenderError> {
+  let (instance, _) = vulkano_objects::instance::create_headless(false)?;
+  vulkano_objects::physical_device::print_devices(&instance);
+  Ok(())
+}
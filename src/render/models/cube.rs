@@ -1,5 +1,31 @@
 use crate::render::{models::Model, vertex_data::Vertex3d};
 
+/// Corners of the cube, indexed by `FACES`
+const CORNERS: [[f32; 3]; 8] = [
+  [-1.0, -1.0, -1.0],
+  [1.0, -1.0, -1.0],
+  [1.0, 1.0, -1.0],
+  [-1.0, 1.0, -1.0],
+  [-1.0, -1.0, 1.0],
+  [1.0, -1.0, 1.0],
+  [1.0, 1.0, 1.0],
+  [-1.0, 1.0, 1.0],
+];
+
+/// Texture coordinates of the face corners (a, b, c, d)
+const FACE_UVS: [[f32; 2]; 4] = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+
+/// Corners (a, b, c, d) of each face and its normal. Each face is drawn as the
+/// triangles (a, b, d) and (d, b, c), which are clockwise when seen from outside.
+const FACES: [([usize; 4], [f32; 3]); 6] = [
+  ([0, 1, 2, 3], [0.0, 0.0, -1.0]),
+  ([1, 5, 6, 2], [1.0, 0.0, 0.0]),
+  ([5, 4, 7, 6], [0.0, 0.0, 1.0]),
+  ([4, 0, 3, 7], [-1.0, 0.0, 0.0]),
+  ([3, 2, 6, 7], [0.0, 1.0, 0.0]),
+  ([4, 5, 1, 0], [0.0, -1.0, 0.0]),
+];
+
 pub struct CubeModel {
   vertices: Vec<Vertex3d>,
   indices: Vec<u16>,
@@ -7,38 +33,29 @@ pub struct CubeModel {
 
 impl CubeModel {
   pub fn new() -> Self {
-    Self {
-      vertices: vec![
-        Vertex3d {
-          position: [-1.0, -1.0, -1.0],
-        },
-        Vertex3d {
-          position: [1.0, -1.0, -1.0],
-        },
-        Vertex3d {
-          position: [1.0, 1.0, -1.0],
-        },
-        Vertex3d {
-          position: [-1.0, 1.0, -1.0],
-        },
-        Vertex3d {
-          position: [-1.0, -1.0, 1.0],
-        },
-        Vertex3d {
-          position: [1.0, -1.0, 1.0],
-        },
-        Vertex3d {
-          position: [1.0, 1.0, 1.0],
-        },
-        Vertex3d {
-          position: [-1.0, 1.0, 1.0],
-        },
-      ],
-      indices: vec![
-        0, 1, 3, 3, 1, 2, 1, 5, 2, 2, 5, 6, 5, 4, 6, 6, 4, 7, 4, 0, 7, 7, 0, 3, 3, 2, 7, 7, 2, 6,
-        4, 5, 0, 0, 5, 1,
-      ],
-    }
+    // each face has its own vertices, so that they can have different normals
+    let vertices = FACES
+      .iter()
+      .flat_map(|&(corners, normal)| {
+        corners
+          .into_iter()
+          .zip(FACE_UVS)
+          .map(move |(corner, uv)| Vertex3d {
+            position: CORNERS[corner],
+            normal,
+            uv,
+          })
+      })
+      .collect();
+
+    let indices = (0..FACES.len() as u16)
+      .flat_map(|face| {
+        let first = face * 4;
+        [0, 1, 3, 3, 1, 2].map(|i| first + i)
+      })
+      .collect();
+
+    Self { vertices, indices }
   }
 }
 
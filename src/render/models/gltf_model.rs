@@ -0,0 +1,175 @@
+use crate::render::{
+  models::{normals::calculate_smooth_normals, Model, ModelLoadError, TextureSource},
+  vertex_data::Vertex3d,
+};
+use gltf::{image::Format, mesh::Mode};
+use std::{path::Path, sync::Arc};
+
+/// Single mesh loaded from a glTF 2.0 file (`.gltf` or `.glb`).
+///
+/// All triangle primitives of the mesh get merged into one model, other primitive modes
+/// (points, lines, strips) are skipped. Incomplete triangles at the end of a primitive are
+/// dropped, so that they don't shift the triangles of the next one. Primitives without normals
+/// get smooth normals calculated from their triangles.
+///
+/// The texture is the base color texture of the first primitive that has one, as only a single
+/// texture per model is supported.
+#[derive(Clone)]
+pub struct GltfModel {
+  vertices: Vec<Vertex3d>,
+  indices: Vec<u16>,
+  texture: Option<TextureSource>,
+}
+
+impl GltfModel {
+  /// Loads every mesh in the file, in the same order as their indices
+  pub fn load_all(path: impl AsRef<Path>) -> Result<Vec<Self>, ModelLoadError> {
+    let path = path.as_ref();
+    let (document, buffers, images) = gltf::import(path)?;
+
+    // images can be used by multiple meshes, so they only get converted once
+    let textures: Vec<Option<TextureSource>> = images
+      .into_iter()
+      .enumerate()
+      .map(|(i, image)| {
+        into_rgba(image.format, image.pixels).map(|pixels| TextureSource::Pixels {
+          name: format!("{}#image{}", path.display(), i),
+          dimensions: [image.width, image.height],
+          pixels: Arc::new(pixels),
+        })
+      })
+      .collect();
+
+    document
+      .meshes()
+      .map(|mesh| {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let mut texture = None;
+
+        for primitive in mesh.primitives() {
+          if primitive.mode() != Mode::Triangles {
+            continue;
+          }
+
+          let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+          let positions: Vec<[f32; 3]> = match reader.read_positions() {
+            Some(positions) => positions.collect(),
+            None => continue,
+          };
+          let mut primitive_indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..positions.len() as u32).collect(),
+          };
+          primitive_indices.truncate(primitive_indices.len() / 3 * 3);
+
+          let offset = vertices.len();
+          let vertex_count = offset + positions.len();
+          if vertex_count > u16::MAX as usize + 1 {
+            return Err(ModelLoadError::TooManyVertices(vertex_count));
+          }
+          if primitive_indices
+            .iter()
+            .any(|&i| i as usize >= positions.len())
+          {
+            return Err(ModelLoadError::InvalidData(format!(
+              "mesh {} has out of range indices",
+              mesh.index()
+            )));
+          }
+
+          // glTF is y-up with counter-clockwise triangles, while this project is y-down with
+          // clockwise triangles, and negating y converts both at the same time
+          let positions: Vec<[f32; 3]> = positions.into_iter().map(flip_y).collect();
+          let base_color = primitive
+            .material()
+            .pbr_metallic_roughness()
+            .base_color_texture();
+          let uvs: Vec<[f32; 2]> = match base_color
+            .as_ref()
+            .and_then(|info| reader.read_tex_coords(info.tex_coord()))
+          {
+            Some(uvs) => uvs.into_f32().collect(),
+            None => vec![[0.0, 0.0]; positions.len()],
+          };
+          if texture.is_none() {
+            texture = base_color
+              .and_then(|info| textures.get(info.texture().source().index()).cloned())
+              .flatten();
+          }
+
+          let normals: Vec<[f32; 3]> = match reader.read_normals() {
+            Some(normals) => normals.map(flip_y).collect(),
+            None => calculate_smooth_normals(
+              &positions,
+              &primitive_indices
+                .iter()
+                .map(|&i| i as u16)
+                .collect::<Vec<u16>>(),
+            ),
+          };
+
+          vertices.extend(positions.into_iter().zip(normals).zip(uvs).map(
+            |((position, normal), uv)| Vertex3d {
+              position,
+              normal,
+              uv,
+            },
+          ));
+          indices.extend(
+            primitive_indices
+              .into_iter()
+              .map(|i| (offset + i as usize) as u16),
+          );
+        }
+
+        Ok(Self {
+          vertices,
+          indices,
+          texture,
+        })
+      })
+      .collect()
+  }
+}
+
+impl Model<Vertex3d> for GltfModel {
+  fn get_vertices(&self) -> &Vec<Vertex3d> {
+    &self.vertices
+  }
+
+  fn get_indices(&self) -> &Vec<u16> {
+    &self.indices
+  }
+
+  fn get_texture(&self) -> Option<&TextureSource> {
+    self.texture.as_ref()
+  }
+}
+
+/// Converts 8 bit images to RGBA, returns None for other formats
+fn into_rgba(format: Format, pixels: Vec<u8>) -> Option<Vec<u8>> {
+  let channels = match format {
+    Format::R8G8B8A8 => return Some(pixels),
+    Format::R8 => 1,
+    Format::R8G8 => 2,
+    Format::R8G8B8 => 3,
+    _ => return None,
+  };
+
+  Some(
+    pixels
+      .chunks_exact(channels)
+      .flat_map(|pixel| match *pixel {
+        [r] => [r, r, r, 255],
+        [r, g] => [r, g, 0, 255],
+        [r, g, b] => [r, g, b, 255],
+        _ => unreachable!(),
+      })
+      .collect(),
+  )
+}
+
+fn flip_y([x, y, z]: [f32; 3]) -> [f32; 3] {
+  [x, -y, z]
+}
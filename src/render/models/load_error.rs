@@ -0,0 +1,68 @@
+use crate::game_objects::TransformError;
+use std::fmt;
+
+/// Errors that can happen when loading a model from a file
+#[derive(Debug)]
+pub enum ModelLoadError {
+  Io(std::io::Error),
+  /// The file contents are not valid, `line` starts at 1
+  Parse {
+    line: usize,
+    message: String,
+  },
+  /// Indices are stored as `u16`, so a single model can't have more vertices than that
+  TooManyVertices(usize),
+  /// The requested object or group doesn't exist in the file
+  MissingGroup(String),
+  Gltf(gltf::Error),
+  /// The file could be read, but its contents don't make sense
+  InvalidData(String),
+  /// The glTF file doesn't contain a mesh with this index
+  MissingMesh(usize),
+  /// A texture image couldn't be read or decoded
+  Image(image::ImageError),
+  /// A glTF node, named by its name or index, has a transformation that objects can't represent
+  NodeTransform {
+    node: String,
+    error: TransformError,
+  },
+}
+
+impl fmt::Display for ModelLoadError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ModelLoadError::Io(e) => write!(f, "io error: {}", e),
+      ModelLoadError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+      ModelLoadError::TooManyVertices(count) => write!(
+        f,
+        "model has {} vertices, but at most {} are supported",
+        count,
+        u16::MAX as usize + 1
+      ),
+      ModelLoadError::MissingGroup(name) => write!(f, "no object or group named \"{}\"", name),
+      ModelLoadError::Gltf(e) => write!(f, "invalid glTF file: {}", e),
+      ModelLoadError::InvalidData(message) => write!(f, "{}", message),
+      ModelLoadError::MissingMesh(i) => write!(f, "no mesh with index {}", i),
+      ModelLoadError::Image(e) => write!(f, "invalid texture: {}", e),
+      ModelLoadError::NodeTransform { node, error } => write!(f, "node \"{}\": {}", node, error),
+    }
+  }
+}
+
+impl From<std::io::Error> for ModelLoadError {
+  fn from(e: std::io::Error) -> Self {
+    ModelLoadError::Io(e)
+  }
+}
+
+impl From<gltf::Error> for ModelLoadError {
+  fn from(e: gltf::Error) -> Self {
+    ModelLoadError::Gltf(e)
+  }
+}
+
+impl From<image::ImageError> for ModelLoadError {
+  fn from(e: image::ImageError) -> Self {
+    ModelLoadError::Image(e)
+  }
+}
@@ -2,10 +2,19 @@
 /// There reside all "models" which contain vertices and indices
 
 mod cube;
+mod gltf_model;
+mod load_error;
+mod normals;
+mod obj_model;
 mod square;
+mod texture_source;
 mod traits;
 
 pub use traits::Model;
 
 pub use cube::CubeModel;
+pub use gltf_model::GltfModel;
+pub use load_error::ModelLoadError;
+pub use obj_model::ObjModel;
 pub use square::SquareModel;
+pub use texture_source::TextureSource;
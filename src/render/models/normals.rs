@@ -0,0 +1,30 @@
+use cgmath::{InnerSpace, Vector3, Zero};
+
+/// Calculates a normal for each vertex by adding the normals of every triangle that uses it,
+/// weighted by their area.
+///
+/// Triangles are expected to be clockwise when seen from the front, like every model in this project.
+pub fn calculate_smooth_normals(positions: &[[f32; 3]], indices: &[u16]) -> Vec<[f32; 3]> {
+  let mut normals = vec![Vector3::zero(); positions.len()];
+
+  for triangle in indices.chunks_exact(3) {
+    let [a, b, c] =
+      [triangle[0], triangle[1], triangle[2]].map(|i| Vector3::from(positions[i as usize]));
+    // cross product of a clockwise triangle points to its back, so the operands are swapped
+    let normal = (c - a).cross(b - a);
+    for &i in triangle {
+      normals[i as usize] += normal;
+    }
+  }
+
+  normals
+    .into_iter()
+    .map(|normal| {
+      if normal.magnitude2() > 0.0 {
+        normal.normalize().into()
+      } else {
+        [0.0, 0.0, 0.0]
+      }
+    })
+    .collect()
+}
@@ -0,0 +1,606 @@
+use crate::render::{
+  models::{normals::calculate_smooth_normals, Model, ModelLoadError, TextureSource},
+  vertex_data::Vertex3d,
+};
+use std::{
+  collections::HashMap,
+  fs, io,
+  path::{Path, PathBuf},
+};
+
+/// Model loaded from a Wavefront OBJ file.
+///
+/// Vertex positions, texture coordinates, normals and faces are read. Faces with more than three
+/// vertices get triangulated and each object (`o`) or group (`g`) becomes a separate model.
+/// Vertices without normals get smooth normals calculated from the faces that use them.
+///
+/// The texture is the diffuse map (`map_Kd`) of the material used by the first face of the group.
+/// Other material properties are ignored.
+pub struct ObjModel {
+  name: String,
+  vertices: Vec<Vertex3d>,
+  indices: Vec<u16>,
+  material: Option<String>,
+  texture: Option<TextureSource>,
+}
+
+impl ObjModel {
+  /// Loads a single object / group from the file, or all of them merged together if `group` is None
+  pub fn load(path: impl AsRef<Path>, group: Option<&str>) -> Result<Self, ModelLoadError> {
+    let models = Self::load_all(path)?;
+
+    match group {
+      Some(name) => models
+        .into_iter()
+        .find(|model| model.name == name)
+        .ok_or_else(|| ModelLoadError::MissingGroup(name.to_string())),
+      None => Self::merge("merged".to_string(), models),
+    }
+  }
+
+  /// Names of every object / group that contains faces, without loading their materials
+  pub fn load_group_names(path: impl AsRef<Path>) -> Result<Vec<String>, ModelLoadError> {
+    let source = fs::read_to_string(path)?;
+    Ok(
+      Self::parse(&source)?
+        .into_iter()
+        .map(|model| model.name)
+        .collect(),
+    )
+  }
+
+  /// Loads every object / group that contains faces as a different model
+  pub fn load_all(path: impl AsRef<Path>) -> Result<Vec<Self>, ModelLoadError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path)?;
+    let mut models = Self::parse(&source)?;
+
+    // material libraries are relative to the OBJ file and textures to their library
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut textures: HashMap<String, PathBuf> = HashMap::new();
+    for library in material_libraries(&source) {
+      let library = directory.join(library);
+      let library_source = match fs::read_to_string(&library) {
+        Ok(library_source) => library_source,
+        // exporters often reference libraries that don't get shipped with the model
+        Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+        Err(e) => return Err(e.into()),
+      };
+
+      let library_directory = library.parent().unwrap_or_else(|| Path::new(""));
+      for (material, texture) in parse_material_textures(&library_source) {
+        textures.insert(material, library_directory.join(texture));
+      }
+    }
+
+    for model in models.iter_mut() {
+      model.texture = model
+        .material
+        .as_ref()
+        .and_then(|material| textures.get(material))
+        .map(|texture| TextureSource::File(texture.clone()));
+    }
+
+    Ok(models)
+  }
+
+  /// Reads every object / group, where faces that appear before any of them go to "default".
+  /// Objects and groups with the same name get joined, so that names are unique.
+  pub fn parse(source: &str) -> Result<Vec<Self>, ModelLoadError> {
+    let mut attributes = Attributes::default();
+    let mut groups = vec![GroupBuilder::new("default".to_string())];
+    let mut current = 0;
+    let mut material: Option<String> = None;
+
+    for (line_i, line) in source.lines().enumerate() {
+      let line_n = line_i + 1;
+      let line = line.split('#').next().unwrap_or("");
+      let mut tokens = line.split_whitespace();
+
+      match tokens.next() {
+        Some("v") => attributes
+          .positions
+          .push(parse_vector3(tokens, "vertex positions", line_n)?),
+        Some("vt") => attributes
+          .uvs
+          .push(parse_texture_coordinates(tokens, line_n)?),
+        Some("vn") => attributes
+          .normals
+          .push(parse_vector3(tokens, "vertex normals", line_n)?),
+        Some("f") => {
+          let face = tokens
+            .map(|token| parse_face_vertex(token, &attributes, line_n))
+            .collect::<Result<Vec<FaceVertex>, ModelLoadError>>()?;
+          if face.len() < 3 {
+            return Err(ModelLoadError::Parse {
+              line: line_n,
+              message: "faces need at least 3 vertices".to_string(),
+            });
+          }
+
+          let group = &mut groups[current];
+          if group.material.is_none() {
+            group.material = material.clone();
+          }
+          group.add_face(&face, &attributes)?;
+        }
+        Some("usemtl") => material = Some(tokens.collect::<Vec<&str>>().join(" ")),
+        Some("o") | Some("g") => {
+          let name = tokens.collect::<Vec<&str>>().join(" ");
+          current = match groups.iter().position(|group| group.name == name) {
+            Some(i) => i,
+            None => {
+              groups.push(GroupBuilder::new(name));
+              groups.len() - 1
+            }
+          };
+        }
+        // material libraries get read by `load_all` and everything else is ignored
+        _ => {}
+      }
+    }
+
+    Ok(
+      groups
+        .into_iter()
+        .filter(|group| !group.indices.is_empty())
+        .map(|group| group.build())
+        .collect(),
+    )
+  }
+
+  /// Joins multiple models into a single one, which keeps the first texture found
+  pub fn merge(name: String, models: Vec<ObjModel>) -> Result<Self, ModelLoadError> {
+    let vertex_count = models.iter().map(|model| model.vertices.len()).sum();
+    if vertex_count > u16::MAX as usize + 1 {
+      return Err(ModelLoadError::TooManyVertices(vertex_count));
+    }
+
+    let mut vertices = Vec::with_capacity(vertex_count);
+    let mut indices = Vec::new();
+    let mut material = None;
+    let mut texture = None;
+    for model in models {
+      let offset = vertices.len() as u16;
+      indices.extend(model.indices.iter().map(|i| i + offset));
+      vertices.extend(model.vertices);
+
+      if texture.is_none() && model.texture.is_some() {
+        material = model.material;
+        texture = model.texture;
+      }
+    }
+
+    Ok(Self {
+      name,
+      vertices,
+      indices,
+      material,
+      texture,
+    })
+  }
+}
+
+impl Model<Vertex3d> for ObjModel {
+  fn get_vertices(&self) -> &Vec<Vertex3d> {
+    &self.vertices
+  }
+
+  fn get_indices(&self) -> &Vec<u16> {
+    &self.indices
+  }
+
+  fn get_texture(&self) -> Option<&TextureSource> {
+    self.texture.as_ref()
+  }
+}
+
+/// Every vertex attribute read so far, shared by all groups
+#[derive(Default)]
+struct Attributes {
+  positions: Vec<[f32; 3]>,
+  uvs: Vec<[f32; 2]>,
+  normals: Vec<[f32; 3]>,
+}
+
+/// Zero based attribute indices of a single face vertex
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct FaceVertex {
+  position: usize,
+  uv: Option<usize>,
+  normal: Option<usize>,
+}
+
+/// Collects vertices of a single object / group, where indices are local to it
+struct GroupBuilder {
+  name: String,
+  /// Material of the first face
+  material: Option<String>,
+  vertices: Vec<Vertex3d>,
+  indices: Vec<u16>,
+  /// Vertices that had no normal in the file
+  missing_normals: Vec<u16>,
+  /// Maps attribute indices of the whole file to indices local to this group
+  local_indices: HashMap<FaceVertex, u16>,
+}
+
+impl GroupBuilder {
+  fn new(name: String) -> Self {
+    Self {
+      name,
+      material: None,
+      vertices: Vec::new(),
+      indices: Vec::new(),
+      missing_normals: Vec::new(),
+      local_indices: HashMap::new(),
+    }
+  }
+
+  fn add_face(
+    &mut self,
+    face: &[FaceVertex],
+    attributes: &Attributes,
+  ) -> Result<(), ModelLoadError> {
+    let local = face
+      .iter()
+      .map(|&vertex| self.get_local_index(vertex, attributes))
+      .collect::<Result<Vec<u16>, ModelLoadError>>()?;
+
+    for i in 1..(local.len() - 1) {
+      self
+        .indices
+        .extend_from_slice(&[local[0], local[i], local[i + 1]]);
+    }
+
+    Ok(())
+  }
+
+  fn get_local_index(
+    &mut self,
+    vertex: FaceVertex,
+    attributes: &Attributes,
+  ) -> Result<u16, ModelLoadError> {
+    if let Some(&local) = self.local_indices.get(&vertex) {
+      return Ok(local);
+    }
+
+    if self.vertices.len() > u16::MAX as usize {
+      return Err(ModelLoadError::TooManyVertices(self.vertices.len() + 1));
+    }
+
+    let local = self.vertices.len() as u16;
+    let normal = match vertex.normal {
+      Some(i) => flip_y(attributes.normals[i]),
+      None => {
+        self.missing_normals.push(local);
+        [0.0, 0.0, 0.0]
+      }
+    };
+    // OBJ texture coordinates start at the bottom left corner of the image
+    let uv = match vertex.uv {
+      Some(i) => [attributes.uvs[i][0], 1.0 - attributes.uvs[i][1]],
+      None => [0.0, 0.0],
+    };
+    self.vertices.push(Vertex3d {
+      position: flip_y(attributes.positions[vertex.position]),
+      normal,
+      uv,
+    });
+    self.local_indices.insert(vertex, local);
+
+    Ok(local)
+  }
+
+  fn build(mut self) -> ObjModel {
+    if !self.missing_normals.is_empty() {
+      let positions: Vec<[f32; 3]> = self.vertices.iter().map(|v| v.position).collect();
+      let normals = calculate_smooth_normals(&positions, &self.indices);
+      for &i in self.missing_normals.iter() {
+        self.vertices[i as usize].normal = normals[i as usize];
+      }
+    }
+
+    ObjModel {
+      name: self.name,
+      vertices: self.vertices,
+      indices: self.indices,
+      material: self.material,
+      texture: None,
+    }
+  }
+}
+
+/// OBJ files are y-up with counter-clockwise faces, while this project is y-down with
+/// clockwise faces, and negating y converts both at the same time
+fn flip_y([x, y, z]: [f32; 3]) -> [f32; 3] {
+  [x, -y, z]
+}
+
+fn parse_vector3<'a>(
+  tokens: impl Iterator<Item = &'a str>,
+  name: &str,
+  line: usize,
+) -> Result<[f32; 3], ModelLoadError> {
+  // a fourth "w" component may exist in positions, but it only matters for rational curves
+  let values = tokens
+    .take(3)
+    .map(|token| {
+      token.parse::<f32>().map_err(|_| ModelLoadError::Parse {
+        line,
+        message: format!("invalid number \"{}\"", token),
+      })
+    })
+    .collect::<Result<Vec<f32>, ModelLoadError>>()?;
+
+  if values.len() != 3 {
+    return Err(ModelLoadError::Parse {
+      line,
+      message: format!("{} need 3 components", name),
+    });
+  }
+
+  Ok([values[0], values[1], values[2]])
+}
+
+/// Parses `u [v [w]]`, where the missing `v` defaults to 0 and `w` is ignored
+fn parse_texture_coordinates<'a>(
+  mut tokens: impl Iterator<Item = &'a str>,
+  line: usize,
+) -> Result<[f32; 2], ModelLoadError> {
+  let parse = |token: &str| {
+    token.parse::<f32>().map_err(|_| ModelLoadError::Parse {
+      line,
+      message: format!("invalid number \"{}\"", token),
+    })
+  };
+
+  let u = match tokens.next() {
+    Some(token) => parse(token)?,
+    None => {
+      return Err(ModelLoadError::Parse {
+        line,
+        message: "texture coordinates need at least 1 component".to_string(),
+      })
+    }
+  };
+  let v = match tokens.next() {
+    Some(token) => parse(token)?,
+    None => 0.0,
+  };
+
+  Ok([u, v])
+}
+
+/// Parses a face vertex (`v`, `v/vt`, `v//vn` or `v/vt/vn`)
+fn parse_face_vertex(
+  token: &str,
+  attributes: &Attributes,
+  line: usize,
+) -> Result<FaceVertex, ModelLoadError> {
+  let mut parts = token.split('/');
+  let position = parse_index(parts.next().unwrap_or(""), attributes.positions.len(), line)?;
+  let uv = match parts.next() {
+    Some(part) if !part.is_empty() => Some(parse_index(part, attributes.uvs.len(), line)?),
+    _ => None,
+  };
+  let normal = match parts.next() {
+    Some(part) if !part.is_empty() => Some(parse_index(part, attributes.normals.len(), line)?),
+    _ => None,
+  };
+
+  Ok(FaceVertex {
+    position,
+    uv,
+    normal,
+  })
+}
+
+/// Returns the zero based index of an attribute.
+///
+/// Negative indices are relative to the number of attributes read so far.
+fn parse_index(value: &str, count: usize, line: usize) -> Result<usize, ModelLoadError> {
+  let invalid = || ModelLoadError::Parse {
+    line,
+    message: format!("invalid index \"{}\"", value),
+  };
+
+  let index: isize = value.parse().map_err(|_| invalid())?;
+  let resolved = if index > 0 {
+    index - 1
+  } else if index < 0 {
+    count as isize + index
+  } else {
+    return Err(invalid());
+  };
+
+  if resolved < 0 || resolved as usize >= count {
+    return Err(ModelLoadError::Parse {
+      line,
+      message: format!("index {} is out of range", index),
+    });
+  }
+
+  Ok(resolved as usize)
+}
+
+/// Returns the file names of every material library (`mtllib`) used in an OBJ file
+fn material_libraries(source: &str) -> Vec<&str> {
+  source
+    .lines()
+    .filter_map(|line| {
+      let mut tokens = line.split('#').next().unwrap_or("").split_whitespace();
+      match tokens.next() {
+        Some("mtllib") => Some(tokens),
+        _ => None,
+      }
+    })
+    .flatten()
+    .collect()
+}
+
+/// Returns the diffuse texture (`map_Kd`) of every material in a MTL file that has one
+fn parse_material_textures(source: &str) -> Vec<(String, String)> {
+  let mut textures = Vec::new();
+  let mut material: Option<String> = None;
+
+  for line in source.lines() {
+    let line = line.split('#').next().unwrap_or("");
+    let mut tokens = line.split_whitespace();
+
+    match tokens.next() {
+      Some("newmtl") => material = Some(tokens.collect::<Vec<&str>>().join(" ")),
+      // options like "-s 1 1 1" may come before the file name, which is always last
+      Some("map_Kd") => {
+        if let (Some(material), Some(file)) = (&material, tokens.last()) {
+          textures.push((material.clone(), file.to_string()));
+        }
+      }
+      _ => {}
+    }
+  }
+
+  textures
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse_one(source: &str) -> ObjModel {
+    let mut models = ObjModel::parse(source).unwrap();
+    assert_eq!(models.len(), 1);
+    models.remove(0)
+  }
+
+  fn positions(model: &ObjModel) -> Vec<[f32; 3]> {
+    model.vertices.iter().map(|v| v.position).collect()
+  }
+
+  #[test]
+  fn triangulates_faces() {
+    let model = parse_one(
+      "v 0 0 0\n\
+       v 1 0 0\n\
+       v 1 1 0\n\
+       v 0 1 0\n\
+       f 1 2 3 4\n",
+    );
+
+    assert_eq!(model.name, "default");
+    assert_eq!(
+      positions(&model),
+      vec![
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, -1.0, 0.0],
+        [0.0, -1.0, 0.0]
+      ]
+    );
+    assert_eq!(model.indices, vec![0, 1, 2, 0, 2, 3]);
+  }
+
+  #[test]
+  fn reads_uvs_and_normals() {
+    let model = parse_one(
+      "v 0 0 0\n\
+       v 1 0 0\n\
+       v 0 1 0\n\
+       vt 0 0\n\
+       vt 1 0.25\n\
+       vn 0 1 0\n\
+       f 1/1/1 2/2/1 3//1\n",
+    );
+
+    assert_eq!(model.vertices[0].uv, [0.0, 1.0]);
+    assert_eq!(model.vertices[1].uv, [1.0, 0.75]);
+    assert_eq!(model.vertices[2].uv, [0.0, 0.0]);
+    for vertex in model.vertices.iter() {
+      assert_eq!(vertex.normal, [0.0, -1.0, 0.0]);
+    }
+  }
+
+  #[test]
+  fn resolves_negative_indices() {
+    let relative = parse_one(
+      "v 0 0 0\n\
+       v 1 0 0\n\
+       v 0 1 0\n\
+       f -3 -2 -1\n",
+    );
+    let absolute = parse_one(
+      "v 0 0 0\n\
+       v 1 0 0\n\
+       v 0 1 0\n\
+       f 1 2 3\n",
+    );
+
+    assert_eq!(positions(&relative), positions(&absolute));
+    assert_eq!(relative.indices, absolute.indices);
+  }
+
+  #[test]
+  fn rejects_invalid_indices() {
+    for face in ["f 1 2 4", "f 0 1 2", "f -4 1 2", "f 1 2 x"] {
+      let source = format!("v 0 0 0\nv 1 0 0\nv 0 1 0\n{}\n", face);
+      match ObjModel::parse(&source) {
+        Err(ModelLoadError::Parse { line, .. }) => assert_eq!(line, 4),
+        _ => panic!("\"{}\" should fail to parse", face),
+      }
+    }
+  }
+
+  #[test]
+  fn calculates_missing_normals() {
+    let model = parse_one(
+      "v 0 0 0\n\
+       v 1 0 0\n\
+       v 0 1 0\n\
+       v 1 1 0\n\
+       vn 1 0 0\n\
+       f 1 2 3\n\
+       f 2//1 4//1 3//1\n",
+    );
+
+    // vertices without normals face the same way as the face that uses them
+    let expected = calculate_smooth_normals(&positions(&model), &model.indices);
+    for (vertex, expected) in model.vertices.iter().zip(expected).take(3) {
+      assert_eq!(vertex.normal, expected);
+      assert_ne!(vertex.normal, [0.0, 0.0, 0.0]);
+    }
+    for vertex in model.vertices.iter().skip(3) {
+      assert_eq!(vertex.normal, [1.0, 0.0, 0.0]);
+    }
+  }
+
+  #[test]
+  fn splits_objects_and_groups() {
+    let models = ObjModel::parse(
+      "v 0 0 0\n\
+       v 1 0 0\n\
+       v 0 1 0\n\
+       v 1 1 0\n\
+       o empty\n\
+       o first\n\
+       usemtl red\n\
+       f 1 2 3\n\
+       g second\n\
+       usemtl blue\n\
+       f 2 4 3\n\
+       g first\n\
+       f 2 4 3\n",
+    )
+    .unwrap();
+
+    let names: Vec<&str> = models.iter().map(|model| model.name.as_str()).collect();
+    assert_eq!(names, vec!["first", "second"]);
+
+    // vertices are local to each group and a repeated name continues the same group
+    assert_eq!(models[0].vertices.len(), 4);
+    assert_eq!(models[0].indices, vec![0, 1, 2, 1, 3, 2]);
+    assert_eq!(models[1].vertices.len(), 3);
+    assert_eq!(models[1].indices, vec![0, 1, 2]);
+
+    assert_eq!(models[0].material.as_deref(), Some("red"));
+    assert_eq!(models[1].material.as_deref(), Some("blue"));
+  }
+}
@@ -7,25 +7,32 @@ pub struct SquareModel {
 
 impl SquareModel {
   pub fn new() -> Self {
+    let corners = [
+      ([-0.5, -0.5, 0.0], [0.0, 0.0]),
+      ([0.5, -0.5, 0.0], [1.0, 0.0]),
+      ([-0.5, 0.5, 0.0], [0.0, 1.0]),
+      ([0.5, 0.5, 0.0], [1.0, 1.0]),
+    ];
+
+    // both sides have their own vertices, as their normals point in opposite directions
+    let vertices = [[0.0, 0.0, 1.0], [0.0, 0.0, -1.0]]
+      .iter()
+      .flat_map(|&normal| {
+        corners.map(|(position, uv)| Vertex3d {
+          position,
+          normal,
+          uv,
+        })
+      })
+      .collect();
+
     Self {
-      vertices: vec![
-        Vertex3d {
-          position: [-0.5, -0.5, 0.0],
-        },
-        Vertex3d {
-          position: [0.5, -0.5, 0.0],
-        },
-        Vertex3d {
-          position: [-0.5, 0.5, 0.0],
-        },
-        Vertex3d {
-          position: [0.5, 0.5, 0.0],
-        },
-      ],
+      vertices,
       // draw clockwise and counter-clockwise
       // 0  1
       // 2  3
-      indices: vec![0, 2, 1, 1, 2, 3, 0, 1, 2, 1, 3, 2],
+      // (the same for 4 to 7)
+      indices: vec![0, 2, 1, 1, 2, 3, 4, 5, 6, 5, 7, 6],
     }
   }
 }
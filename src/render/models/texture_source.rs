@@ -0,0 +1,39 @@
+use crate::render::models::ModelLoadError;
+use std::{path::PathBuf, sync::Arc};
+
+/// Image that a model uses as its base color
+#[derive(Clone, Debug)]
+pub enum TextureSource {
+  /// Image file in any format supported by the `image` crate
+  File(PathBuf),
+  /// Already decoded RGBA pixels, for example an image embedded in a glTF file.
+  /// `name` identifies the image, so that models using the same one can share it.
+  Pixels {
+    name: String,
+    dimensions: [u32; 2],
+    pixels: Arc<Vec<u8>>,
+  },
+}
+
+impl TextureSource {
+  /// Models with textures that have the same key can share a single image
+  pub fn key(&self) -> String {
+    match self {
+      TextureSource::File(path) => path.to_string_lossy().into_owned(),
+      TextureSource::Pixels { name, .. } => name.clone(),
+    }
+  }
+
+  /// Returns the image dimensions and its pixels as 8 bit RGBA
+  pub fn load(&self) -> Result<([u32; 2], Vec<u8>), ModelLoadError> {
+    match self {
+      TextureSource::File(path) => {
+        let image = image::open(path)?.to_rgba8();
+        Ok((image.dimensions().into(), image.into_raw()))
+      }
+      TextureSource::Pixels {
+        dimensions, pixels, ..
+      } => Ok((*dimensions, pixels.as_ref().clone())),
+    }
+  }
+}
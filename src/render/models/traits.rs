@@ -1,9 +1,15 @@
+use crate::render::models::TextureSource;
 use vulkano::buffer::BufferContents;
 
 /// Main trait of every model.
-/// 
+///
 /// Has functions for retrieving indices and vertices
 pub trait Model<V: BufferContents> {
   fn get_indices(&self) -> &Vec<u16>;
   fn get_vertices(&self) -> &Vec<V>;
+
+  /// Image sampled for the base color, models without one are drawn with flat colors
+  fn get_texture(&self) -> Option<&TextureSource> {
+    None
+  }
 }
@@ -0,0 +1,112 @@
+use crate::render::{vulkano_objects, RenderError};
+use std::sync::Arc;
+use vulkano::{
+  buffer::{BufferUsage, CpuAccessibleBuffer},
+  command_buffer::PrimaryAutoCommandBuffer,
+  device::{Device, Queue},
+  format::Format,
+  image::{AttachmentImage, ImageUsage},
+  render_pass::{Framebuffer, RenderPass},
+};
+
+/// Format of the offscreen color image. Each pixel gets copied as 4 bytes into the output buffer.
+pub const OFFSCREEN_COLOR_FORMAT: Format = Format::R8G8B8A8_SRGB;
+
+/// Headless counterpart of `SwapchainContainer`.
+///
+/// Manages a single offscreen color image that gets drawn into and a cpu accessible buffer
+/// where its contents get copied to after each frame.
+pub struct OffscreenContainer {
+  dimensions: [u32; 2],
+  render_pass: Arc<RenderPass>,
+  framebuffers: Vec<Arc<Framebuffer>>,
+  output_buffer: Arc<CpuAccessibleBuffer<[u8]>>,
+  copy_command_buffer: Arc<PrimaryAutoCommandBuffer>,
+}
+
+impl OffscreenContainer {
+  pub fn new(
+    device: Arc<Device>,
+    graphics_queue: Arc<Queue>,
+    dimensions: [u32; 2],
+    samples: u32,
+  ) -> Result<Self, RenderError> {
+    let color_image = AttachmentImage::with_usage(
+      device.clone(),
+      dimensions,
+      OFFSCREEN_COLOR_FORMAT,
+      ImageUsage {
+        color_attachment: true,
+        transfer_source: true,
+        ..ImageUsage::none()
+      },
+    )?;
+
+    let depth_format = vulkano_objects::render_pass::choose_depth_format(device.physical_device());
+    let shared_attachments = vulkano_objects::framebuffers::create_shared_attachments(
+      device.clone(),
+      dimensions,
+      OFFSCREEN_COLOR_FORMAT,
+      depth_format,
+      samples,
+    )?;
+
+    let render_pass = vulkano_objects::render_pass::create(
+      device.clone(),
+      OFFSCREEN_COLOR_FORMAT,
+      depth_format,
+      samples,
+    );
+    let framebuffers = vec![vulkano_objects::framebuffers::create_offscreen(
+      render_pass.clone(),
+      color_image.clone(),
+      &shared_attachments,
+    )];
+
+    let output_buffer = CpuAccessibleBuffer::from_iter(
+      device.clone(),
+      BufferUsage {
+        transfer_destination: true,
+        ..BufferUsage::none()
+      },
+      true,
+      (0..dimensions[0] * dimensions[1] * 4).map(|_| 0u8),
+    )?;
+
+    let copy_command_buffer = vulkano_objects::command_buffers::create_image_to_buffer_copy(
+      device,
+      graphics_queue,
+      color_image,
+      output_buffer.clone(),
+    )?;
+
+    Ok(Self {
+      dimensions,
+      render_pass,
+      framebuffers,
+      output_buffer,
+      copy_command_buffer,
+    })
+  }
+
+  pub fn get_render_pass(&self) -> Arc<RenderPass> {
+    self.render_pass.clone()
+  }
+
+  /// Contains a single framebuffer, so that it can be used in the same places as the swapchain ones
+  pub fn get_framebuffers(&self) -> &Vec<Arc<Framebuffer>> {
+    &self.framebuffers
+  }
+
+  pub fn get_output_buffer(&self) -> Arc<CpuAccessibleBuffer<[u8]>> {
+    self.output_buffer.clone()
+  }
+
+  pub fn get_copy_command_buffer(&self) -> Arc<PrimaryAutoCommandBuffer> {
+    self.copy_command_buffer.clone()
+  }
+
+  pub fn dimensions(&self) -> [u32; 2] {
+    self.dimensions
+  }
+}
@@ -0,0 +1,310 @@
+use crate::render::{
+  models::ModelLoadError, vulkano_objects::physical_device::DeviceSelectionError,
+};
+use std::fmt;
+use vulkano::{
+  buffer::cpu_access::{ReadLockError, WriteLockError},
+  command_buffer::{
+    AutoCommandBufferBuilderContextError, BeginRenderPassError, BuildError, CommandBufferExecError,
+    CopyBufferError, CopyBufferImageError, DispatchError, DrawIndexedIndirectError,
+  },
+  descriptor_set::DescriptorSetCreationError,
+  device::{physical::SurfacePropertiesError, DeviceCreationError},
+  image::ImageCreationError,
+  instance::InstanceCreationError,
+  memory::DeviceMemoryAllocationError,
+  shader::ShaderCreationError,
+  swapchain::{AcquireError, SwapchainCreationError},
+  sync::FlushError,
+  OomError,
+};
+
+/// Errors that can happen while creating the renderer or drawing a frame.
+///
+/// Vulkano errors that mean the device, the memory or the surface are gone get turned into
+/// [`RenderError::DeviceLost`], [`RenderError::OutOfMemory`] and [`RenderError::SurfaceLost`],
+/// so that callers don't have to look into every error type.
+#[derive(Debug)]
+pub enum RenderError {
+  /// The driver stopped the device, for example after a reset or a timeout
+  DeviceLost,
+  /// The gpu or the host ran out of memory
+  OutOfMemory,
+  /// The window surface can't be presented to anymore
+  SurfaceLost,
+  InstanceCreation(InstanceCreationError),
+  DeviceSelection(DeviceSelectionError),
+  DeviceCreation(DeviceCreationError),
+  SurfaceCreation(vulkano_win::CreationError),
+  /// Capabilities, formats or present modes of the surface couldn't be queried
+  SurfaceProperties(SurfacePropertiesError),
+  SwapchainCreation(SwapchainCreationError),
+  AcquireImage(AcquireError),
+  Flush(FlushError),
+  /// A command buffer couldn't be submitted
+  Execute(CommandBufferExecError),
+  /// A buffer or image couldn't be allocated for a reason other than running out of memory
+  Allocation(DeviceMemoryAllocationError),
+  ImageCreation(ImageCreationError),
+  CommandBufferBuild(BuildError),
+  /// A command couldn't be recorded into a command buffer
+  CommandBufferRecord(RecordError),
+  ShaderCreation(ShaderCreationError),
+  /// A model or texture of the scene couldn't be loaded, `name` says which one
+  ModelLoad {
+    name: String,
+    error: ModelLoadError,
+  },
+  DescriptorSetCreation(DescriptorSetCreationError),
+  /// A buffer written by the cpu is still being used, by the cpu or the gpu
+  WriteLock(WriteLockError),
+  /// A buffer read by the cpu is still being written to by the gpu
+  ReadLock(ReadLockError),
+  /// The copy of an offscreen image doesn't have as many bytes as its dimensions need
+  OutputSize {
+    dimensions: [u32; 2],
+    len: usize,
+  },
+}
+
+/// Errors returned by the commands that get recorded into [`RenderError::CommandBufferRecord`]
+#[derive(Debug)]
+pub enum RecordError {
+  BeginRenderPass(BeginRenderPassError),
+  EndRenderPass(AutoCommandBufferBuilderContextError),
+  DrawIndexedIndirect(DrawIndexedIndirectError),
+  Dispatch(DispatchError),
+  CopyBuffer(CopyBufferError),
+  CopyImageToBuffer(CopyBufferImageError),
+}
+
+impl fmt::Display for RecordError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      RecordError::BeginRenderPass(e) => write!(f, "begin render pass: {}", e),
+      RecordError::EndRenderPass(e) => write!(f, "end render pass: {}", e),
+      RecordError::DrawIndexedIndirect(e) => write!(f, "indirect draw: {}", e),
+      RecordError::Dispatch(e) => write!(f, "dispatch: {}", e),
+      RecordError::CopyBuffer(e) => write!(f, "buffer copy: {}", e),
+      RecordError::CopyImageToBuffer(e) => write!(f, "image to buffer copy: {}", e),
+    }
+  }
+}
+
+impl fmt::Display for RenderError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      RenderError::DeviceLost => write!(f, "the device was lost"),
+      RenderError::OutOfMemory => write!(f, "out of memory"),
+      RenderError::SurfaceLost => write!(f, "the window surface was lost"),
+      RenderError::InstanceCreation(e) => write!(f, "failed to create the instance: {}", e),
+      RenderError::DeviceSelection(e) => write!(f, "failed to select a device: {}", e),
+      RenderError::DeviceCreation(e) => write!(f, "failed to create the device: {}", e),
+      RenderError::SurfaceCreation(e) => write!(f, "failed to create the window surface: {}", e),
+      RenderError::SurfaceProperties(e) => {
+        write!(f, "failed to query the window surface: {}", e)
+      }
+      RenderError::SwapchainCreation(e) => write!(f, "failed to create the swapchain: {}", e),
+      RenderError::AcquireImage(e) => write!(f, "failed to acquire a swapchain image: {}", e),
+      RenderError::Flush(e) => write!(f, "failed to flush the frame: {}", e),
+      RenderError::Execute(e) => write!(f, "failed to execute a command buffer: {}", e),
+      RenderError::Allocation(e) => write!(f, "failed to allocate memory: {}", e),
+      RenderError::ImageCreation(e) => write!(f, "failed to create an image: {}", e),
+      RenderError::CommandBufferBuild(e) => write!(f, "failed to build a command buffer: {}", e),
+      RenderError::CommandBufferRecord(e) => write!(f, "failed to record a command: {}", e),
+      RenderError::ShaderCreation(e) => write!(f, "failed to create a shader module: {}", e),
+      RenderError::ModelLoad { name, error } => write!(f, "failed to load {}: {}", name, error),
+      RenderError::DescriptorSetCreation(e) => {
+        write!(f, "failed to create a descriptor set: {}", e)
+      }
+      RenderError::WriteLock(e) => write!(f, "failed to write to a buffer: {}", e),
+      RenderError::ReadLock(e) => write!(f, "failed to read a buffer: {}", e),
+      RenderError::OutputSize { dimensions, len } => write!(
+        f,
+        "{} bytes can't hold a {}x{} image",
+        len, dimensions[0], dimensions[1]
+      ),
+    }
+  }
+}
+
+impl From<OomError> for RenderError {
+  fn from(_: OomError) -> Self {
+    RenderError::OutOfMemory
+  }
+}
+
+impl From<InstanceCreationError> for RenderError {
+  fn from(e: InstanceCreationError) -> Self {
+    match e {
+      InstanceCreationError::OomError(_) => RenderError::OutOfMemory,
+      e => RenderError::InstanceCreation(e),
+    }
+  }
+}
+
+impl From<DeviceSelectionError> for RenderError {
+  fn from(e: DeviceSelectionError) -> Self {
+    RenderError::DeviceSelection(e)
+  }
+}
+
+impl From<DeviceCreationError> for RenderError {
+  fn from(e: DeviceCreationError) -> Self {
+    match e {
+      DeviceCreationError::DeviceLost => RenderError::DeviceLost,
+      DeviceCreationError::OutOfHostMemory | DeviceCreationError::OutOfDeviceMemory => {
+        RenderError::OutOfMemory
+      }
+      e => RenderError::DeviceCreation(e),
+    }
+  }
+}
+
+impl From<vulkano_win::CreationError> for RenderError {
+  fn from(e: vulkano_win::CreationError) -> Self {
+    RenderError::SurfaceCreation(e)
+  }
+}
+
+impl From<SurfacePropertiesError> for RenderError {
+  fn from(e: SurfacePropertiesError) -> Self {
+    match e {
+      SurfacePropertiesError::OomError(_) => RenderError::OutOfMemory,
+      SurfacePropertiesError::SurfaceLost => RenderError::SurfaceLost,
+      e => RenderError::SurfaceProperties(e),
+    }
+  }
+}
+
+impl From<SwapchainCreationError> for RenderError {
+  fn from(e: SwapchainCreationError) -> Self {
+    match e {
+      SwapchainCreationError::OomError(_) => RenderError::OutOfMemory,
+      SwapchainCreationError::DeviceLost => RenderError::DeviceLost,
+      SwapchainCreationError::SurfaceLost => RenderError::SurfaceLost,
+      e => RenderError::SwapchainCreation(e),
+    }
+  }
+}
+
+impl From<AcquireError> for RenderError {
+  fn from(e: AcquireError) -> Self {
+    match e {
+      AcquireError::OomError(_) => RenderError::OutOfMemory,
+      AcquireError::DeviceLost => RenderError::DeviceLost,
+      AcquireError::SurfaceLost => RenderError::SurfaceLost,
+      e => RenderError::AcquireImage(e),
+    }
+  }
+}
+
+impl From<FlushError> for RenderError {
+  fn from(e: FlushError) -> Self {
+    match e {
+      FlushError::OomError(_) => RenderError::OutOfMemory,
+      FlushError::DeviceLost => RenderError::DeviceLost,
+      FlushError::SurfaceLost => RenderError::SurfaceLost,
+      e => RenderError::Flush(e),
+    }
+  }
+}
+
+impl From<CommandBufferExecError> for RenderError {
+  fn from(e: CommandBufferExecError) -> Self {
+    RenderError::Execute(e)
+  }
+}
+
+impl From<DeviceMemoryAllocationError> for RenderError {
+  fn from(e: DeviceMemoryAllocationError) -> Self {
+    match e {
+      DeviceMemoryAllocationError::OomError(_) => RenderError::OutOfMemory,
+      e => RenderError::Allocation(e),
+    }
+  }
+}
+
+impl From<ImageCreationError> for RenderError {
+  fn from(e: ImageCreationError) -> Self {
+    match e {
+      ImageCreationError::AllocError(e) => e.into(),
+      e => RenderError::ImageCreation(e),
+    }
+  }
+}
+
+impl From<BuildError> for RenderError {
+  fn from(e: BuildError) -> Self {
+    match e {
+      BuildError::OomError(_) => RenderError::OutOfMemory,
+      e => RenderError::CommandBufferBuild(e),
+    }
+  }
+}
+
+impl From<ShaderCreationError> for RenderError {
+  fn from(e: ShaderCreationError) -> Self {
+    match e {
+      ShaderCreationError::OomError(_) => RenderError::OutOfMemory,
+      e => RenderError::ShaderCreation(e),
+    }
+  }
+}
+
+impl From<DescriptorSetCreationError> for RenderError {
+  fn from(e: DescriptorSetCreationError) -> Self {
+    match e {
+      DescriptorSetCreationError::OomError(_) => RenderError::OutOfMemory,
+      e => RenderError::DescriptorSetCreation(e),
+    }
+  }
+}
+
+impl From<WriteLockError> for RenderError {
+  fn from(e: WriteLockError) -> Self {
+    RenderError::WriteLock(e)
+  }
+}
+
+impl From<ReadLockError> for RenderError {
+  fn from(e: ReadLockError) -> Self {
+    RenderError::ReadLock(e)
+  }
+}
+
+impl From<BeginRenderPassError> for RenderError {
+  fn from(e: BeginRenderPassError) -> Self {
+    RenderError::CommandBufferRecord(RecordError::BeginRenderPass(e))
+  }
+}
+
+impl From<AutoCommandBufferBuilderContextError> for RenderError {
+  fn from(e: AutoCommandBufferBuilderContextError) -> Self {
+    RenderError::CommandBufferRecord(RecordError::EndRenderPass(e))
+  }
+}
+
+impl From<DrawIndexedIndirectError> for RenderError {
+  fn from(e: DrawIndexedIndirectError) -> Self {
+    RenderError::CommandBufferRecord(RecordError::DrawIndexedIndirect(e))
+  }
+}
+
+impl From<DispatchError> for RenderError {
+  fn from(e: DispatchError) -> Self {
+    RenderError::CommandBufferRecord(RecordError::Dispatch(e))
+  }
+}
+
+impl From<CopyBufferError> for RenderError {
+  fn from(e: CopyBufferError) -> Self {
+    RenderError::CommandBufferRecord(RecordError::CopyBuffer(e))
+  }
+}
+
+impl From<CopyBufferImageError> for RenderError {
+  fn from(e: CopyBufferImageError) -> Self {
+    RenderError::CommandBufferRecord(RecordError::CopyImageToBuffer(e))
+  }
+}
@@ -1,149 +1,428 @@
 use crate::{
-  render::{renderer::Renderer, Camera},
+  render::{
+    frame_ring::FrameRing, graphics_pipelines::ViewMode, renderer::Renderer, Camera, RenderError,
+    RenderSettings,
+  },
   Scene,
 };
+use log::{debug, warn};
 use std::sync::Arc;
 use vulkano::{
-  swapchain::{AcquireError, PresentFuture},
+  swapchain::{AcquireError, PresentFuture, PresentMode},
   sync::{FenceSignalFuture, FlushError, GpuFuture},
 };
 use winit::{event_loop::EventLoop, window::Window};
 
-type FenceFuture = FenceSignalFuture<PresentFuture<Box<dyn GpuFuture>, Window>>;
+type FenceFuture = FenceSignalFuture<PresentFuture<Box<dyn GpuFuture>, Arc<Window>>>;
 
 /// Manages synchronization with fences and is responsible for keeping all components working together
 /// each frame.
 ///
-/// The program operates on a number of main command buffers equal to the framebuffer count, as each
-/// operates on that specific framebuffer. These are not recreated each frame, so (for simplicity) the number of frames
-/// in flight is equal to the number of command buffers, and corelate directly with the swapchain image index.
+/// Frames in flight go around in a ring, each with its own fence and resources written by the cpu.
+/// Their number doesn't depend on the swapchain image count, so it stays the same with every driver.
+/// Main command buffers are not recreated each frame, so there is one for each pair of frame in
+/// flight and swapchain image.
 pub struct RenderLoop {
-  renderer: Renderer,
-  recreate_swapchain: bool,
-  window_resized: bool,
-  fences: Vec<Option<Arc<FenceFuture>>>,
-  previous_fence_i: usize,
-  update_buffer_models: Vec<bool>,
+  /// Only `None` after rebuilding it failed, in which case the error was returned
+  renderer: Option<Renderer>,
+  state: LoopState<Arc<FenceFuture>>,
+  /// Returned by the next update instead of drawing, see [`RenderLoop::inject_error`]
+  #[cfg(debug_assertions)]
+  injected_error: Option<RenderError>,
 }
 
 impl<'a> RenderLoop {
-  pub fn new(event_loop: &EventLoop<()>, scene: &Scene) -> Self {
-    let renderer = Renderer::initialize(event_loop, scene);
+  pub fn new(
+    event_loop: &EventLoop<()>,
+    scene: &Scene,
+    settings: &RenderSettings,
+  ) -> Result<Self, RenderError> {
+    let state = LoopState::new(settings.frames_in_flight);
+    let renderer = Renderer::initialize(event_loop, scene, settings)?;
 
-    let frames_in_flight = renderer.get_image_count();
-    let fences = vec![None; frames_in_flight];
-
-    Self {
-      renderer,
-      recreate_swapchain: false,
-      window_resized: false,
-      fences,
-      previous_fence_i: 0,
-      update_buffer_models: vec![false; frames_in_flight],
-    }
+    Ok(Self {
+      renderer: Some(renderer),
+      state,
+      #[cfg(debug_assertions)]
+      injected_error: None,
+    })
   }
 
   /// - Handles window resizing and swapchain recreation;
+  /// - Waits for the fence of the next frame in flight
   /// - Acquires next swapchain image
-  /// - Waits for fences
+  /// - Updates buffers of the frame if the scene changed since they were last written
   /// - Updates components calls for buffer update commands
   /// - Flushes next future
-  pub fn update(&mut self, camera: &Camera, scene: &Scene) {
-    if self.window_resized {
-      self.window_resized = false;
-      self.recreate_swapchain = false;
+  ///
+  /// An out of date swapchain only skips the frame. When the device or the surface get lost,
+  /// the renderer gets rebuilt from the scene and drawing continues in the next update.
+  /// Every other error gets returned.
+  pub fn update(&mut self, camera: &Camera, scene: &Scene) -> Result<(), RenderError> {
+    #[cfg(debug_assertions)]
+    let injected_error = self.injected_error.take();
+    #[cfg(not(debug_assertions))]
+    let injected_error = None;
+
+    let simulated = injected_error.is_some();
+    let result = match injected_error {
+      Some(error) => Err(error),
+      None => self.draw_frame(camera, scene),
+    };
+
+    let renderer = &mut self.renderer;
+    self.state.handle_result(result, simulated, || {
+      // the old surface and swapchain have to be gone before new ones get created
+      let old = renderer.take().expect("The renderer failed to be rebuilt");
+      *renderer = Some(old.rebuild(scene)?);
+      Ok(())
+    })
+  }
+
+  fn renderer(&self) -> &Renderer {
+    self
+      .renderer
+      .as_ref()
+      .expect("The renderer failed to be rebuilt")
+  }
+
+  fn renderer_mut(&mut self) -> &mut Renderer {
+    self
+      .renderer
+      .as_mut()
+      .expect("The renderer failed to be rebuilt")
+  }
+
+  fn draw_frame(&mut self, camera: &Camera, scene: &Scene) -> Result<(), RenderError> {
+    if self.state.window_resized {
+      self.state.window_resized = false;
+      self.state.recreate_swapchain = false;
       // automatically recreates the swapchain
-      self.renderer.handle_window_resize();
+      self.renderer_mut().handle_window_resize()?;
     }
-    if self.recreate_swapchain {
-      self.recreate_swapchain = false;
-      self.renderer.recreate_swapchain();
+    if self.state.recreate_swapchain {
+      self.state.recreate_swapchain = false;
+      self.renderer_mut().recreate_swapchain()?;
     }
 
     // for tests
     // std::thread::sleep(std::time::Duration::from_millis(1000));
 
-    let (image_i, suboptimal, acquire_future) = match self.renderer.acquire_next_swapchain_image() {
+    let frame_i = self.state.frames.next_frame_i();
+
+    // This is the only place where the cpu waits for the gpu
+    if let Some(fence) = self.state.frames.fence_to_wait() {
+      fence.wait(None)?;
+    }
+
+    // before acquiring, so that changes aren't missed when the frame gets skipped
+    if scene.objects_changed {
+      self.state.frames.invalidate_buffers();
+    }
+
+    let (image_i, suboptimal, acquire_future) = match self.renderer().acquire_next_swapchain_image()
+    {
       Ok(r) => r,
       Err(AcquireError::OutOfDate) => {
-        self.recreate_swapchain = true;
-        return;
+        self.state.recreate_swapchain = true;
+        return Ok(());
       }
-      Err(e) => panic!("Failed to acquire next image: {:?}", e),
+      Err(e) => return Err(e.into()),
     };
 
     if suboptimal {
-      self.recreate_swapchain = true;
+      self.state.recreate_swapchain = true;
     }
 
-    let oldest_fence_exists = if let Some(cur_fence) = &mut self.fences[image_i] {
-      // current fence will be the oldest flushed one, so waiting it won't wait upon the other futures execution
-      cur_fence.wait(None).unwrap();
-      true
-    } else {
-      false
-    };
-
-    if scene.objects_changed {
-      self.update_buffer_models.fill(true);
-    }
-    let update_buffer_models = self.update_buffer_models[image_i];
-    self.update_buffer_models[image_i] = false;
-
-    if let Some(fence) = &mut self.fences[self.previous_fence_i].clone() {
-      let something_needs_all_gpu_resources = update_buffer_models;
-      if something_needs_all_gpu_resources || !oldest_fence_exists {
-        // This fence corresponds to the earliest flushed one, so waiting it will block the CPU until GPU finishes all operations
-        fence.wait(None).unwrap();
-      }
-
-      // todo: Currently there is a single instance buffer where in the main execution future other buffers get copied to it
-      // When a copy operation happens, the current source and destination buffers get locked, which means they should automatically
-      // unlock after calling "cur_fence.wait()", because it is supposed to clean and unlock all unused resources
-      // However, buffers for the current frame continue to be locked
-      // See "self.renderer.flush_next_future" for more information about execution
-      // I will do more research for this, but for now "something_needs_all_gpu_resources" will be true when this operation happens
-      if update_buffer_models {
-        self.renderer.update_buffer_models(image_i, scene);
-      }
+    // When the instance buffers grow, the ones of every frame get recreated empty, which is fine
+    // because it only happens in the first update after objects were added, so the other frames
+    // are still waiting for their update
+    if self.state.frames.take_buffer_update() {
+      self.renderer_mut().update_buffer_models(frame_i, scene)?;
     }
 
-    let previous_future = match self.fences[self.previous_fence_i].clone() {
-      None => self.renderer.synchronize().boxed(),
+    let previous_future = match self.state.frames.previous_fence().cloned() {
+      None => self.renderer().synchronize().boxed(),
       Some(fence) => fence.boxed(),
     };
 
-    let result = self.renderer.flush_next_future(
+    let result = self.renderer().flush_next_future(
       previous_future,
       acquire_future,
+      frame_i,
       image_i,
       camera,
       scene.total_object_count,
     );
 
-    self.fences[image_i] = match result {
+    let fence = match result {
       Ok(fence) => Some(Arc::new(fence)),
-      Err(FlushError::OutOfDate) => {
-        println!("out of date");
-        self.recreate_swapchain = true;
-        None
-      }
-      Err(e) => {
-        println!("Failed to flush future: {:?}", e);
+      Err(RenderError::Flush(FlushError::OutOfDate)) => {
+        debug!("Swapchain out of date");
+        self.state.recreate_swapchain = true;
         None
       }
+      Err(e) => return Err(e),
     };
 
-    self.previous_fence_i = image_i;
+    self.state.frames.finish_frame(fence);
+    Ok(())
+  }
+
+  /// Makes the next update behave as if drawing failed with `error`, without touching the gpu.
+  /// Used to check that the renderer recovers from losing the device or the surface.
+  ///
+  /// Only exists in debug builds, so that release builds can't be made to rebuild on purpose.
+  #[cfg(debug_assertions)]
+  pub fn inject_error(&mut self, error: RenderError) {
+    self.injected_error = Some(error);
   }
 
   /// Signal that window should be handled in the next update
   pub fn handle_window_resize(&mut self) {
-    self.window_resized = true;
+    self.state.window_resized = true;
+  }
+
+  /// Turns vsync on or off by recreating the swapchain, returning the new present mode
+  pub fn toggle_vsync(&mut self) -> Result<PresentMode, RenderError> {
+    self.renderer_mut().toggle_vsync()
+  }
+
+  /// Switches to the next view mode that the device supports, returning it
+  pub fn next_view_mode(&mut self) -> ViewMode {
+    self.renderer_mut().next_view_mode()
   }
 
   /// Returns surface window
   pub fn get_window(&self) -> &Window {
-    self.renderer.get_surface_window()
+    self.renderer().get_surface_window()
+  }
+}
+
+/// Fences that recovering waits for, so that they can be dropped without panicking
+trait RecoverableFence {
+  fn wait_before_drop(&self) -> Result<(), RenderError>;
+}
+
+impl RecoverableFence for Arc<FenceFuture> {
+  fn wait_before_drop(&self) -> Result<(), RenderError> {
+    self.wait(None).map_err(RenderError::from)
+  }
+}
+
+/// Frames in flight and what has to be recreated before drawing the next one.
+///
+/// Kept apart from the renderer, which only gets used through the `rebuild` closures,
+/// so that recovering from a lost device or surface can be tested without a device.
+struct LoopState<F> {
+  frames: FrameRing<F>,
+  recreate_swapchain: bool,
+  window_resized: bool,
+}
+
+impl<F: RecoverableFence> LoopState<F> {
+  fn new(frames_in_flight: usize) -> Self {
+    Self {
+      frames: FrameRing::new(frames_in_flight),
+      recreate_swapchain: false,
+      window_resized: false,
+    }
+  }
+
+  /// Recovers from losing the device or the surface, returning every other result unchanged.
+  /// `simulated` is set when the error was injected, so the device still works.
+  fn handle_result(
+    &mut self,
+    result: Result<(), RenderError>,
+    simulated: bool,
+    rebuild: impl FnOnce() -> Result<(), RenderError>,
+  ) -> Result<(), RenderError> {
+    match result {
+      Err(RenderError::DeviceLost) => self.recover(true, simulated, rebuild),
+      Err(RenderError::SurfaceLost) => self.recover(false, simulated, rebuild),
+      result => result,
+    }
+  }
+
+  /// Rebuilds the renderer and starts again from the first frame in flight
+  fn recover(
+    &mut self,
+    device_lost: bool,
+    simulated: bool,
+    rebuild: impl FnOnce() -> Result<(), RenderError>,
+  ) -> Result<(), RenderError> {
+    warn!(
+      "The {} was lost{}, recreating the renderer",
+      if device_lost { "device" } else { "surface" },
+      if simulated { " (simulated)" } else { "" }
+    );
+
+    // new buffers are empty, so every frame has to update them
+    for fence in self.frames.reset() {
+      // dropping a future waits for it and panics if that fails, which always happens
+      // with a lost device, so its resources get leaked instead. Otherwise they get freed,
+      // including the old swapchain, which has to be gone before the rebuild creates a new one.
+      if (device_lost && !simulated) || fence.wait_before_drop().is_err() {
+        std::mem::forget(fence);
+      }
+    }
+
+    rebuild()?;
+
+    self.recreate_swapchain = false;
+    self.window_resized = false;
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::{cell::Cell, rc::Rc};
+
+  /// Counts how many times fences get waited for and dropped
+  #[derive(Clone, Default)]
+  struct Counts {
+    waits: Rc<Cell<usize>>,
+    drops: Rc<Cell<usize>>,
+  }
+
+  struct Fence {
+    counts: Counts,
+    /// Whether waiting fails, like it does with a lost device
+    lost: bool,
+  }
+
+  impl RecoverableFence for Fence {
+    fn wait_before_drop(&self) -> Result<(), RenderError> {
+      self.counts.waits.set(self.counts.waits.get() + 1);
+      if self.lost {
+        Err(RenderError::DeviceLost)
+      } else {
+        Ok(())
+      }
+    }
+  }
+
+  impl Drop for Fence {
+    fn drop(&mut self) {
+      self.counts.drops.set(self.counts.drops.get() + 1);
+    }
+  }
+
+  /// State with two flushed frames out of three and both flags set
+  fn flushed_state(counts: &Counts, lost: bool) -> LoopState<Fence> {
+    let mut state = LoopState::new(3);
+    for _ in 0..2 {
+      state.frames.finish_frame(Some(Fence {
+        counts: counts.clone(),
+        lost,
+      }));
+    }
+    state.recreate_swapchain = true;
+    state.window_resized = true;
+    state
+  }
+
+  fn assert_restarted(state: &mut LoopState<Fence>) {
+    assert!(!state.recreate_swapchain);
+    assert!(!state.window_resized);
+    assert_eq!(state.frames.next_frame_i(), 0);
+    assert!(state.frames.fence_to_wait().is_none());
+    assert!(state.frames.previous_fence().is_none());
+    for _ in 0..3 {
+      assert!(state.frames.take_buffer_update());
+      state.frames.finish_frame(None);
+    }
+  }
+
+  /// Recovers from `error` and checks that the renderer got rebuilt once
+  fn recover(state: &mut LoopState<Fence>, error: RenderError, simulated: bool) {
+    let mut rebuilds = 0;
+    let result = state.handle_result(Err(error), simulated, || {
+      rebuilds += 1;
+      Ok(())
+    });
+
+    assert!(result.is_ok());
+    assert_eq!(rebuilds, 1);
+    assert_restarted(state);
+  }
+
+  #[test]
+  fn device_lost_forgets_fences() {
+    let counts = Counts::default();
+    let mut state = flushed_state(&counts, true);
+
+    recover(&mut state, RenderError::DeviceLost, false);
+
+    assert_eq!(counts.waits.get(), 0);
+    assert_eq!(counts.drops.get(), 0);
+  }
+
+  #[test]
+  fn simulated_device_lost_waits_and_drops_fences() {
+    let counts = Counts::default();
+    let mut state = flushed_state(&counts, false);
+
+    recover(&mut state, RenderError::DeviceLost, true);
+
+    assert_eq!(counts.waits.get(), 2);
+    assert_eq!(counts.drops.get(), 2);
+  }
+
+  #[test]
+  fn surface_lost_waits_and_drops_fences() {
+    let counts = Counts::default();
+    let mut state = flushed_state(&counts, false);
+
+    recover(&mut state, RenderError::SurfaceLost, false);
+
+    assert_eq!(counts.waits.get(), 2);
+    assert_eq!(counts.drops.get(), 2);
+  }
+
+  #[test]
+  fn failed_waits_forget_fences() {
+    let counts = Counts::default();
+    let mut state = flushed_state(&counts, true);
+
+    recover(&mut state, RenderError::SurfaceLost, true);
+
+    assert_eq!(counts.waits.get(), 2);
+    assert_eq!(counts.drops.get(), 0);
+  }
+
+  #[test]
+  fn other_results_are_returned() {
+    let counts = Counts::default();
+    let mut state = flushed_state(&counts, false);
+
+    let ok = state.handle_result(Ok(()), false, || panic!("rebuilt after a frame was drawn"));
+    let error = state.handle_result(Err(RenderError::OutOfMemory), false, || {
+      panic!("rebuilt after running out of memory")
+    });
+
+    assert!(ok.is_ok());
+    assert!(matches!(error, Err(RenderError::OutOfMemory)));
+    assert!(state.recreate_swapchain);
+    assert!(state.window_resized);
+    assert_eq!(state.frames.next_frame_i(), 2);
+    assert_eq!(counts.waits.get(), 0);
+    assert_eq!(counts.drops.get(), 0);
+  }
+
+  #[test]
+  fn failed_rebuild_is_returned() {
+    let counts = Counts::default();
+    let mut state = flushed_state(&counts, false);
+
+    let result = state.handle_result(Err(RenderError::DeviceLost), false, || {
+      Err(RenderError::OutOfMemory)
+    });
+
+    assert!(matches!(result, Err(RenderError::OutOfMemory)));
+    assert_eq!(state.frames.next_frame_i(), 0);
   }
 }
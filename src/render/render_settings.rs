@@ -0,0 +1,37 @@
+use crate::{render::DeviceChoice, FRAMES_IN_FLIGHT};
+use vulkano::swapchain::PresentMode;
+
+/// Options chosen before creating the renderer, usually from command line arguments
+#[derive(Clone, Debug)]
+pub struct RenderSettings {
+  /// Number of frames that can be processed by the gpu at the same time
+  pub frames_in_flight: usize,
+  /// Preferred present mode, replaced by the closest supported one if the surface doesn't have it.
+  /// Also used when turning vsync off if it isn't FIFO.
+  pub present_mode: PresentMode,
+  /// Use a floating point extended sRGB surface format if the surface supports one
+  pub hdr: bool,
+  /// Use a reverse-Z projection with an infinite far plane, see [`crate::render::Camera::set_reverse_z`]
+  pub reverse_z: bool,
+  /// Samples in each pixel used for multisample anti-aliasing, 1 turns it off.
+  /// Gets reduced to the biggest count that the device supports.
+  pub msaa_samples: u32,
+  /// Enables the Khronos validation layer if it's installed and prints its messages
+  pub validation: bool,
+  /// Device to use instead of the one that gets chosen automatically
+  pub device: Option<DeviceChoice>,
+}
+
+impl Default for RenderSettings {
+  fn default() -> Self {
+    Self {
+      frames_in_flight: FRAMES_IN_FLIGHT,
+      present_mode: PresentMode::Fifo,
+      hdr: false,
+      reverse_z: false,
+      msaa_samples: 1,
+      validation: false,
+      device: None,
+    }
+  }
+}
@@ -1,41 +1,130 @@
 use crate::{
-  game_objects::RenderableIn3d,
+  game_objects::{Material, ModelSource, RenderableIn3d},
   render::{
-    models::{CubeModel, Model, SquareModel},
+    models::{CubeModel, GltfModel, Model, ModelLoadError, ObjModel, SquareModel},
+    uniform_data::ModelData,
     vertex_data::Vertex3d,
+    RenderError,
   },
   Scene,
 };
-use cgmath::Matrix4;
-use std::iter::Iterator;
+use cgmath::{InnerSpace, Matrix4, Vector3};
+use std::{collections::HashMap, iter::Iterator, path::Path};
 
 /// Orders all objects that exists in [`Scene`] so that they can be more easily stored and drawn from Vulkan buffers
 /// todo: should be a macro to implement all Scene objects automatically
 pub struct RenderableScene;
 
 impl RenderableScene {
-  pub fn into_matrices<'a>(scene: &'a Scene) -> impl Iterator<Item = Matrix4<f32>> + '_ {
+  /// Returns the model matrix and material of every object
+  pub fn into_instances<'a>(
+    scene: &'a Scene,
+  ) -> impl Iterator<Item = (Matrix4<f32>, Material)> + '_ {
     // this function took me 2 hours to fix because of a simple error
     // transform into iterator
-    let renderable_cubes = scene.cubes.iter().map(|cube| cube as &dyn RenderableIn3d);
+    let renderable_cubes = scene.cubes().iter().map(|cube| cube as &dyn RenderableIn3d);
 
     let renderable_squares = scene
-      .squares
+      .squares()
       .iter()
       .map(|square| square as &dyn RenderableIn3d);
 
+    // objects of loaded models share the material of their model
+    let loaded = scene.loaded_models().iter().flat_map(|model| {
+      model
+        .objects
+        .iter()
+        .map(move |object| (object.get_model_matrix(), model.material))
+    });
+
     renderable_cubes
       .chain(renderable_squares)
-      .map(|obj| obj.get_model_matrix())
+      .map(|obj| (obj.get_model_matrix(), obj.get_material()))
+      .chain(loaded)
   }
 
   pub fn instance_count_per_model(scene: &Scene) -> Vec<usize> {
-    vec![scene.cubes.len(), scene.squares.len()]
+    let mut counts = vec![scene.cubes().len(), scene.squares().len()];
+    counts.extend(
+      scene
+        .loaded_models()
+        .iter()
+        .map(|model| model.objects.len()),
+    );
+    counts
+  }
+
+  /// Returns the radius of a sphere centered at the origin of each model that contains all of
+  /// its vertices
+  pub fn get_bounding_radii(models: &Vec<Box<dyn Model<Vertex3d>>>) -> Vec<f32> {
+    models
+      .iter()
+      .map(|model| {
+        model
+          .get_vertices()
+          .iter()
+          .map(|vertex| Vector3::from(vertex.position).magnitude())
+          .fold(0.0, f32::max)
+      })
+      .collect()
+  }
+
+  /// Returns the instance range and bounding sphere of each model, in the same order as
+  /// [`RenderableScene::get_models`]
+  pub fn get_model_data(scene: &Scene, bounding_radii: &Vec<f32>) -> Vec<ModelData> {
+    let mut first_instance = 0;
+    RenderableScene::instance_count_per_model(scene)
+      .into_iter()
+      .zip(bounding_radii.iter())
+      .map(|(instance_count, &bounding_radius)| {
+        let data = ModelData {
+          first_instance,
+          instance_count: instance_count as u32,
+          bounding_radius,
+        };
+        first_instance += instance_count as u32;
+        data
+      })
+      .collect()
   }
 
-  pub fn get_models() -> Vec<Box<dyn Model<Vertex3d>>> {
+  /// Returns built-in models followed by every model loaded from a file, in the same order as
+  /// [`RenderableScene::instance_count_per_model`]
+  pub fn get_models(scene: &Scene) -> Result<Vec<Box<dyn Model<Vertex3d>>>, RenderError> {
     let cube_model: Box<dyn Model<Vertex3d>> = Box::new(CubeModel::new());
     let square_model: Box<dyn Model<Vertex3d>> = Box::new(SquareModel::new());
-    vec![cube_model, square_model]
+    let mut models = vec![cube_model, square_model];
+
+    // glTF files may contain many meshes, so each file only gets read once
+    let mut gltf_cache: HashMap<&Path, Vec<GltfModel>> = HashMap::new();
+    for model in scene.loaded_models().iter() {
+      models.push(load_model(&model.source, &mut gltf_cache).map_err(|error| {
+        RenderError::ModelLoad {
+          name: format!("model {:?}", model.source),
+          error,
+        }
+      })?);
+    }
+
+    Ok(models)
+  }
+}
+
+fn load_model<'a>(
+  source: &'a ModelSource,
+  gltf_cache: &mut HashMap<&'a Path, Vec<GltfModel>>,
+) -> Result<Box<dyn Model<Vertex3d>>, ModelLoadError> {
+  match source {
+    ModelSource::Obj { path, group } => Ok(Box::new(ObjModel::load(path, group.as_deref())?)),
+    ModelSource::Gltf { path, mesh } => {
+      if !gltf_cache.contains_key(path.as_path()) {
+        gltf_cache.insert(path.as_path(), GltfModel::load_all(path)?);
+      }
+
+      let model = gltf_cache[path.as_path()]
+        .get(*mesh)
+        .ok_or(ModelLoadError::MissingMesh(*mesh))?;
+      Ok(Box::new(model.clone()))
+    }
   }
 }
@@ -1,25 +1,25 @@
 use crate::{
   render::{
     buffer_container::BufferContainer,
-    shaders::{compute, single_colored},
+    graphics_pipelines::{GraphicsPipelines, ViewMode},
+    shaders::compute,
     swapchain_container::SwapchainContainer,
     vertex_data::{MatrixInstance, Vertex3d},
     vulkano_objects,
-    vulkano_objects::{QueueFamilies, Queues},
-    Camera,
+    vulkano_objects::Queues,
+    Camera, RenderError, RenderSettings,
   },
   Scene,
 };
+use log::info;
 use std::sync::Arc;
 use vulkano::{
   device::{Device, DeviceCreateInfo, DeviceExtensions},
-  instance::Instance,
-  pipeline::{graphics::viewport::Viewport, ComputePipeline, GraphicsPipeline},
-  shader::ShaderModule,
-  swapchain::{AcquireError, PresentFuture, Surface, SwapchainAcquireFuture},
-  sync::{self, FenceSignalFuture, FlushError, GpuFuture, NowFuture},
+  instance::{debug::DebugCallback, Instance},
+  pipeline::{graphics::viewport::Viewport, ComputePipeline},
+  swapchain::{AcquireError, PresentFuture, PresentMode, Surface, SwapchainAcquireFuture},
+  sync::{self, FenceSignalFuture, GpuFuture, NowFuture},
 };
-use vulkano_win::VkSurfaceBuild;
 use winit::{
   event_loop::EventLoop,
   window::{Window, WindowBuilder},
@@ -29,27 +29,97 @@ use winit::{
 ///
 /// Doesn't handle synchronization (see `RenderLoop`).
 pub struct Renderer {
-  surface: Arc<Surface<Window>>,
+  surface: Arc<Surface<Arc<Window>>>,
   _instance: Arc<Instance>,
+  _debug_callback: Option<DebugCallback>,
   device: Arc<Device>,
   queues: Queues,
   swapchain_container: SwapchainContainer,
-  vertex_shader: Arc<ShaderModule>,
-  fragment_shader: Arc<ShaderModule>,
   viewport: Viewport,
-  graphics_pipeline: Arc<GraphicsPipeline>,
+  graphics_pipelines: GraphicsPipelines,
   compute_pipeline: Arc<ComputePipeline>,
   buffer_container: BufferContainer,
+  /// Used when rebuilding after a loss. Its present mode is also used when turning vsync off.
+  settings: RenderSettings,
+  view_mode: ViewMode,
 }
 
 impl<'a> Renderer {
-  /// Creates all main Vulkano objects and saves them
-  pub fn initialize(event_loop: &EventLoop<()>, scene: &Scene) -> Self {
-    let instance = vulkano_objects::instance::create();
+  /// Creates the window and all main Vulkano objects and saves them
+  pub fn initialize(
+    event_loop: &EventLoop<()>,
+    scene: &Scene,
+    settings: &RenderSettings,
+  ) -> Result<Self, RenderError> {
+    let (instance, debug_callback) = vulkano_objects::instance::create(settings.validation)?;
+    let window = Arc::new(
+      WindowBuilder::new()
+        .build(event_loop)
+        .map_err(vulkano_win::CreationError::WindowCreationError)?,
+    );
 
-    let surface = WindowBuilder::new()
-      .build_vk_surface(&event_loop, instance.clone())
-      .unwrap();
+    let mut renderer = Self::create(instance, window, scene, settings, settings.present_mode)?;
+    renderer._debug_callback = debug_callback;
+    Ok(renderer)
+  }
+
+  /// Recreates the surface, the device and everything that depends on them, keeping the window,
+  /// the current present mode and view mode. Objects are filled from the current scene.
+  ///
+  /// Used after the device or the surface get lost, in which case the old objects can't be used
+  /// anymore and only get dropped. Frames in flight have to be finished or forgotten before,
+  /// because a window can only have one swapchain at a time.
+  pub fn rebuild(self, scene: &Scene) -> Result<Self, RenderError> {
+    let Self {
+      surface,
+      _instance: instance,
+      _debug_callback: debug_callback,
+      device,
+      queues,
+      swapchain_container,
+      viewport: _,
+      graphics_pipelines,
+      compute_pipeline,
+      buffer_container,
+      settings,
+      view_mode,
+    } = self;
+    let window = surface.window().clone();
+    let present_mode = swapchain_container.present_mode();
+
+    // everything that uses the old swapchain and surface gets destroyed before creating new ones
+    drop(buffer_container);
+    drop(graphics_pipelines);
+    drop(compute_pipeline);
+    drop(swapchain_container);
+    drop(surface);
+    drop(queues);
+    drop(device);
+
+    let mut rebuilt = Self::create(instance, window, scene, &settings, present_mode)?;
+
+    if rebuilt
+      .graphics_pipelines
+      .available_view_modes()
+      .contains(&view_mode)
+    {
+      rebuilt.view_mode = view_mode;
+    }
+    rebuilt._debug_callback = debug_callback;
+
+    Ok(rebuilt)
+  }
+
+  /// Creates everything that depends on the surface of `window`
+  fn create(
+    instance: Arc<Instance>,
+    window: Arc<Window>,
+    scene: &Scene,
+    settings: &RenderSettings,
+    present_mode: PresentMode,
+  ) -> Result<Self, RenderError> {
+    let surface = vulkano_win::create_surface_from_winit(window, instance.clone())
+      .map_err(vulkano_win::CreationError::SurfaceCreationError)?;
 
     let device_extensions = DeviceExtensions {
       khr_swapchain: true,
@@ -57,30 +127,43 @@ impl<'a> Renderer {
       ..DeviceExtensions::none()
     };
 
-    let (physical_device, queue_families) =
-      vulkano_objects::physical_device::select(&instance, surface.clone(), &device_extensions);
+    let (physical_device, queue_families) = vulkano_objects::physical_device::select(
+      &instance,
+      surface.clone(),
+      &device_extensions,
+      settings.device.as_ref(),
+    )?;
 
-    let (device, mut iter) = Device::new(
+    let (device, queues) = Device::new(
       physical_device,
       DeviceCreateInfo {
         queue_create_infos: queue_families.get_queue_create_info(),
         enabled_extensions: physical_device
           .required_extensions()
           .union(&device_extensions),
+        enabled_features: vulkano_objects::physical_device::get_enabled_features(physical_device),
         ..Default::default()
       },
-    )
-    .expect("failed to create device");
-
-    let queues = QueueFamilies::get_queues(&mut iter);
+    )?;
 
-    let swapchain_container =
-      SwapchainContainer::new(physical_device, device.clone(), surface.clone());
+    let queues = queue_families.get_queues(queues);
+    info!("Queues: {:?}", queues.mapping);
 
-    let vertex_shader =
-      single_colored::vs::load(device.clone()).expect("failed to create shader module");
-    let fragment_shader =
-      single_colored::fs::load(device.clone()).expect("failed to create shader module");
+    let swapchain_container = SwapchainContainer::new(
+      physical_device,
+      device.clone(),
+      surface.clone(),
+      present_mode,
+      settings.hdr,
+      vulkano_objects::render_pass::choose_sample_count(physical_device, settings.msaa_samples),
+    )?;
+    let (format, color_space) = swapchain_container.surface_format();
+    info!(
+      "Surface format: {:?}, color space: {:?}",
+      format, color_space
+    );
+    info!("Present mode: {:?}", swapchain_container.present_mode());
+    info!("MSAA samples: {}", swapchain_container.samples());
 
     let viewport = Viewport {
       origin: [0.0, 0.0],
@@ -88,61 +171,60 @@ impl<'a> Renderer {
       depth_range: 0.0..1.0,
     };
 
-    let graphics_pipeline = vulkano_objects::pipeline::create_graphics(
+    let graphics_pipelines = GraphicsPipelines::new(
       device.clone(),
-      vertex_shader.clone(),
-      fragment_shader.clone(),
       swapchain_container.get_render_pass(),
       viewport.clone(),
+      settings.reverse_z,
     );
 
     let compute_pipeline = vulkano_objects::pipeline::create_compute(
       device.clone(),
-      compute::instance::load(device.clone()).unwrap(),
+      compute::instance::load(device.clone())?,
     );
 
     let buffer_container = BufferContainer::new(
       device.clone(),
       &queue_families,
       &queues,
-      graphics_pipeline.clone(),
+      &graphics_pipelines,
       compute_pipeline.clone(),
       swapchain_container.get_framebuffers(),
+      settings.frames_in_flight,
       scene,
-    );
+    )?;
 
-    Self {
+    Ok(Self {
       surface,
       device,
       queues,
       swapchain_container,
-      vertex_shader,
-      fragment_shader,
       viewport,
-      graphics_pipeline,
+      graphics_pipelines,
       compute_pipeline,
       buffer_container,
+      settings: settings.clone(),
+      view_mode: ViewMode::Shaded,
       _instance: instance,
-    }
+      _debug_callback: None,
+    })
   }
 
-  pub fn recreate_swapchain(&mut self) {
+  pub fn recreate_swapchain(&mut self) -> Result<(), RenderError> {
     self
       .swapchain_container
       .recreate_swapchain(self.device.clone(), self.surface.clone())
   }
 
   /// Recreates swapchain, pipeline and everything that depends on them
-  pub fn handle_window_resize(&mut self) {
+  pub fn handle_window_resize(&mut self) -> Result<(), RenderError> {
     self
       .swapchain_container
-      .recreate_swapchain(self.device.clone(), self.surface.clone());
+      .recreate_swapchain(self.device.clone(), self.surface.clone())?;
     self.viewport.dimensions = self.surface.window().inner_size().into();
 
-    self.graphics_pipeline = vulkano_objects::pipeline::create_graphics(
+    self.graphics_pipelines.recreate(
       self.device.clone(),
-      self.vertex_shader.clone(),
-      self.fragment_shader.clone(),
       self.swapchain_container.get_render_pass(),
       self.viewport.clone(),
     );
@@ -150,18 +232,60 @@ impl<'a> Renderer {
     self.buffer_container.handle_window_resize(
       self.device.clone(),
       self.queues.graphics.clone(),
-      self.graphics_pipeline.clone(),
+      &self.graphics_pipelines,
       self.swapchain_container.get_framebuffers(),
-    );
+    )
   }
 
-  pub fn get_image_count(&self) -> usize {
-    self.swapchain_container.image_count()
+  /// Switches between FIFO (vsync) and the preferred present mode, or the fastest one if the
+  /// preferred is FIFO. Returns the present mode that is used after switching.
+  pub fn toggle_vsync(&mut self) -> Result<PresentMode, RenderError> {
+    let present_mode = if self.swapchain_container.present_mode() == PresentMode::Fifo {
+      let uncapped = match self.settings.present_mode {
+        PresentMode::Fifo => PresentMode::Immediate,
+        mode => mode,
+      };
+      vulkano_objects::swapchain::choose_present_mode(
+        &self.device.physical_device(),
+        &self.surface,
+        uncapped,
+      )?
+    } else {
+      PresentMode::Fifo
+    };
+
+    self.swapchain_container.set_present_mode(
+      self.device.clone(),
+      self.surface.clone(),
+      present_mode,
+    )?;
+    // framebuffers get recreated together with the swapchain
+    self.buffer_container.handle_window_resize(
+      self.device.clone(),
+      self.queues.graphics.clone(),
+      &self.graphics_pipelines,
+      self.swapchain_container.get_framebuffers(),
+    )?;
+
+    Ok(self.swapchain_container.present_mode())
+  }
+
+  /// Switches to the next view mode that the device supports, returning it.
+  /// Main command buffers are recorded for every mode, so nothing gets recreated.
+  pub fn next_view_mode(&mut self) -> ViewMode {
+    let modes = self.graphics_pipelines.available_view_modes();
+    let current = modes
+      .iter()
+      .position(|&mode| mode == self.view_mode)
+      .unwrap();
+    self.view_mode = modes[(current + 1) % modes.len()];
+
+    self.view_mode
   }
 
   pub fn acquire_next_swapchain_image(
     &self,
-  ) -> Result<(usize, bool, SwapchainAcquireFuture<Window>), AcquireError> {
+  ) -> Result<(usize, bool, SwapchainAcquireFuture<Arc<Window>>), AcquireError> {
     self.swapchain_container.acquire_next_swapchain_image()
   }
 
@@ -174,14 +298,18 @@ impl<'a> Renderer {
   }
 
   /// Takes a future and appends all commands that will get executed this frame (in flight)
+  ///
+  /// `frame_i` selects the resources of the frame in flight and `image_i` the swapchain image
+  /// that gets drawn to.
   pub fn flush_next_future(
     &self,
     previous_future: Box<dyn GpuFuture>,
-    swapchain_acquire_future: SwapchainAcquireFuture<Window>,
+    swapchain_acquire_future: SwapchainAcquireFuture<Arc<Window>>,
+    frame_i: usize,
     image_i: usize,
     camera: &Camera,
     instance_count: usize,
-  ) -> Result<FenceSignalFuture<PresentFuture<Box<dyn GpuFuture>, Window>>, FlushError> {
+  ) -> Result<FenceSignalFuture<PresentFuture<Box<dyn GpuFuture>, Arc<Window>>>, RenderError> {
     // join with swapchain future, draw and then present, signal fence and flush
 
     let command_buffers = self.buffer_container.command_buffers();
@@ -193,39 +321,54 @@ impl<'a> Renderer {
         self.device.clone(),
         self.queues.compute.clone(),
         self.compute_pipeline.clone(),
-        descriptor_sets.instance[image_i].clone(),
+        self.buffer_container.buffers(),
+        frame_i,
+        descriptor_sets.instance[frame_i].clone(),
         compute::instance::ty::PushConstantData {
           projection_view: camera.get_projection_view().into(),
+          camera_position: camera.position.to_homogeneous().into(),
+          counts: [
+            instance_count as u32,
+            self.buffer_container.model_count() as u32,
+            0,
+            0,
+          ],
         },
         instance_count,
-      );
+      )?;
 
     let with_main: Box<dyn GpuFuture> = Box::new(
       previous_future
-        .then_execute(self.queues.compute.clone(), instance_compute_command_buffer)
-        .unwrap()
+        .then_execute(self.queues.compute.clone(), instance_compute_command_buffer)?
         .then_signal_semaphore()
         .join(swapchain_acquire_future)
         .then_execute(
           self.queues.graphics.clone(),
-          command_buffers.main[image_i].clone(),
-        )
-        .unwrap(),
+          command_buffers.main[&self.view_mode][frame_i][image_i].clone(),
+        )?,
     );
 
-    with_main
-      .then_swapchain_present(
-        self.queues.graphics.clone(),
-        self.swapchain_container.get_swapchain(),
-        image_i,
-      )
-      .then_signal_fence_and_flush()
+    Ok(
+      with_main
+        .then_swapchain_present(
+          self.queues.graphics.clone(),
+          self.swapchain_container.get_swapchain(),
+          image_i,
+        )
+        .then_signal_fence_and_flush()?,
+    )
   }
 
-  pub fn update_buffer_models(&mut self, buffer_i: usize, scene: &Scene) {
-    self
-      .buffer_container
-      .update_buffer_models(buffer_i, scene);
+  pub fn update_buffer_models(&mut self, frame_i: usize, scene: &Scene) -> Result<(), RenderError> {
+    self.buffer_container.reserve_instances(
+      self.device.clone(),
+      &self.queues,
+      &self.graphics_pipelines,
+      &self.compute_pipeline,
+      self.swapchain_container.get_framebuffers(),
+      scene.total_object_count,
+    )?;
+    self.buffer_container.update_buffer_models(frame_i, scene)
   }
 
   pub fn get_surface_window(&self) -> &Window {
@@ -3,19 +3,121 @@ layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
 
 layout(push_constant) uniform PushConstantData {
   mat4 projection_view;
+  vec4 camera_position;
+  // instance count and model count
+  uvec4 counts;
 } pc;
 
+struct Instance {
+  mat4 matrix;
+  mat4 model;
+  vec4 color;
+  // specular multiplier and shininess
+  vec4 material;
+};
+
+struct Model {
+  uint first_instance;
+  uint instance_count;
+  float bounding_radius;
+};
+
+struct DrawCommand {
+  uint index_count;
+  uint instance_count;
+  uint first_index;
+  int vertex_offset;
+  uint first_instance;
+};
+
 layout(set = 0, binding = 0) readonly buffer InputData {
-  mat4 model[];
+  Instance instances[];
 }
 inputData;
 
 layout(set = 0, binding = 1) buffer OutputData {
-  mat4 matrix[];
+  Instance instances[];
 }
 outputData;
 
+// per frame data used by the graphics pipeline
+layout(set = 0, binding = 2) buffer FrameData {
+  vec4 camera_position;
+}
+frameData;
+
+layout(set = 0, binding = 3) readonly buffer ModelData {
+  Model models[];
+}
+modelData;
+
+// instance counts start at 0 every frame and get incremented for each visible instance
+layout(set = 0, binding = 4) buffer DrawCommands {
+  DrawCommand commands[];
+}
+drawCommands;
+
+// returns the model that the instance belongs to, models are ordered by their first instance
+uint find_model(uint idx) {
+  uint low = 0;
+  uint high = pc.counts.y;
+  while (high - low > 1) {
+    uint middle = (low + high) / 2;
+    if (modelData.models[middle].first_instance <= idx) {
+      low = middle;
+    } else {
+      high = middle;
+    }
+  }
+  return low;
+}
+
+// tests a world space sphere against the frustum planes extracted from projection_view
+// with reverse-Z and an infinite far plane, the last two planes end up being the near plane and
+// a plane right behind the camera, so nothing gets culled by distance
+bool is_visible(vec3 center, float radius) {
+  mat4 rows = transpose(pc.projection_view);
+  vec4 planes[6] = vec4[](
+    rows[3] + rows[0],
+    rows[3] - rows[0],
+    rows[3] + rows[1],
+    rows[3] - rows[1],
+    rows[3] + rows[2],
+    rows[3] - rows[2]
+  );
+
+  for (int i = 0; i < 6; i++) {
+    if (dot(planes[i].xyz, center) + planes[i].w < -radius * length(planes[i].xyz)) {
+      return false;
+    }
+  }
+  return true;
+}
+
 void main() {
   uint idx = gl_GlobalInvocationID.x;
-  outputData.matrix[idx] = pc.projection_view * inputData.model[idx];
-}
\ No newline at end of file
+  if (idx == 0) {
+    frameData.camera_position = pc.camera_position;
+  }
+  if (idx >= pc.counts.x) {
+    return;
+  }
+
+  uint model_i = find_model(idx);
+  Model model = modelData.models[model_i];
+  Instance instance = inputData.instances[idx];
+
+  // objects only have uniform scales, so any column gives the scale
+  float radius = model.bounding_radius * length(instance.model[0].xyz);
+  if (!is_visible(instance.model[3].xyz, radius)) {
+    return;
+  }
+
+  // visible instances of each model are packed at the start of its range
+  uint slot = model.first_instance + atomicAdd(drawCommands.commands[model_i].instance_count, 1u);
+  outputData.instances[slot].matrix = pc.projection_view * instance.model;
+  outputData.instances[slot].model = instance.model;
+  outputData.instances[slot].color = instance.color;
+  // z is unused by the cpu, and gets the object index so that debug colors don't depend on culling
+  outputData.instances[slot].material = vec4(instance.material.xy, float(idx), 0.0);
+}
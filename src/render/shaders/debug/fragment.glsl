@@ -0,0 +1,41 @@
+#version 450
+
+// also keeps the descriptor set 0 layout the same as in the other pipelines
+#include <lighting.glsl>
+
+// 0: normals, 1: depth, 2: instance ids
+layout(constant_id = 0) const uint mode = 0;
+// 1 if the depth buffer uses reverse-Z
+layout(constant_id = 1) const uint reverse_z = 0;
+
+// brightens depth values, which are about near / distance in both depth modes
+#define DEPTH_EXPONENT 0.125
+
+layout(location = 0) in vec3 world_position;
+layout(location = 1) in vec3 world_normal;
+layout(location = 2) flat in uint instance_id;
+
+layout(location = 0) out vec4 fragment_color;
+
+// integer hash, so that consecutive ids get very different colors
+vec3 id_color(uint id) {
+  uint hash = id * 2654435761u;
+  hash ^= hash >> 16;
+  return vec3(hash & 255u, (hash >> 8) & 255u, (hash >> 16) & 255u) / 255.0;
+}
+
+void main() {
+  vec3 color;
+  if (mode == 0u) {
+    color = normalize(world_normal) * 0.5 + 0.5;
+  } else if (mode == 1u) {
+    // the value in the depth buffer, turned around without reverse-Z so that closer is brighter.
+    // Objects that z-fight or lose precision in the depth buffer look the same here
+    float depth = reverse_z == 1u ? gl_FragCoord.z : 1.0 - gl_FragCoord.z;
+    color = vec3(pow(depth, DEPTH_EXPONENT));
+  } else {
+    // lit, so that the shape of objects with the same color can still be seen
+    color = apply_lighting(id_color(instance_id), vec2(0.5, 32.0), world_position, world_normal);
+  }
+  fragment_color = vec4(color, 1.0);
+}
@@ -0,0 +1,23 @@
+#version 450
+
+// vertex data
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+
+// instance data
+layout(location = 2) in mat4 matrix;
+layout(location = 6) in mat4 model;
+layout(location = 11) in vec4 material;
+
+layout(location = 0) out vec3 world_position;
+layout(location = 1) out vec3 world_normal;
+layout(location = 2) flat out uint instance_id;
+
+void main() {
+    world_position = (model * vec4(position, 1.0)).xyz;
+    world_normal = mat3(model) * normal;
+    // index of the object in the scene, written by the compute shader, because gl_InstanceIndex
+    // is the position after culling, which changes when other objects get culled
+    instance_id = uint(material.z);
+    gl_Position = matrix * vec4(position, 1.0);
+}
@@ -0,0 +1,67 @@
+// Lighting shared by every lit fragment shader, uses descriptor set 0
+
+#define MAX_POINT_LIGHTS 4
+
+struct Light {
+  // direction for the directional light, position for point lights
+  vec4 position;
+  vec4 color;
+  // ambient, diffuse, specular and attenuation
+  vec4 intensity;
+};
+
+layout(set = 0, binding = 0) uniform LightingData {
+  Light directional;
+  Light point_lights[MAX_POINT_LIGHTS];
+  uvec4 point_light_count;
+} lighting;
+
+// written by the instance compute shader
+layout(set = 0, binding = 1) readonly buffer FrameData {
+  vec4 camera_position;
+} frame;
+
+// Blinn-Phong, where light_direction points from the surface to the light
+// and material is the specular multiplier and shininess of the object
+vec3 shade(
+  Light light,
+  vec3 base_color,
+  vec2 material,
+  vec3 light_direction,
+  vec3 normal,
+  vec3 view_direction
+) {
+  float diffuse = max(dot(normal, light_direction), 0.0);
+  vec3 halfway = normalize(light_direction + view_direction);
+  float specular = diffuse > 0.0 ? pow(max(dot(normal, halfway), 0.0), material.y) : 0.0;
+
+  vec3 lit_color = (light.intensity.x + diffuse * light.intensity.y) * base_color
+    + specular * material.x * light.intensity.z;
+  return light.color.rgb * lit_color;
+}
+
+vec3 apply_lighting(vec3 base_color, vec2 material, vec3 world_position, vec3 world_normal) {
+  vec3 normal = normalize(world_normal);
+  vec3 view_direction = normalize(frame.camera_position.xyz - world_position);
+
+  vec3 color = shade(
+    lighting.directional,
+    base_color,
+    material,
+    normalize(-lighting.directional.position.xyz),
+    normal,
+    view_direction
+  );
+
+  for (uint i = 0u; i < min(lighting.point_light_count.x, uint(MAX_POINT_LIGHTS)); i++) {
+    Light light = lighting.point_lights[i];
+    vec3 to_light = light.position.xyz - world_position;
+    float distance_squared = dot(to_light, to_light);
+    float attenuation = 1.0 / (1.0 + light.intensity.w * distance_squared);
+
+    color += attenuation
+      * shade(light, base_color, material, normalize(to_light), normal, view_direction);
+  }
+
+  return color;
+}
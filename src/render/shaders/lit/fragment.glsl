@@ -0,0 +1,14 @@
+#version 450
+
+#include <lighting.glsl>
+
+layout(location = 0) in vec3 vertex_color;
+layout(location = 1) in vec3 world_position;
+layout(location = 2) in vec3 world_normal;
+layout(location = 3) in vec2 vertex_material;
+
+layout(location = 0) out vec4 fragment_color;
+
+void main() {
+  fragment_color = vec4(apply_lighting(vertex_color, vertex_material, world_position, world_normal), 1.0);
+}
@@ -0,0 +1,26 @@
+#version 450
+
+// vertex data
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+
+// instance data
+layout(location = 2) in mat4 matrix;
+layout(location = 6) in mat4 model;
+layout(location = 10) in vec4 color;
+layout(location = 11) in vec4 material;
+
+layout(location = 0) out vec3 vertex_color;
+layout(location = 1) out vec3 world_position;
+layout(location = 2) out vec3 world_normal;
+layout(location = 3) out vec2 vertex_material;
+
+void main() {
+    vertex_color = color.rgb;
+    vertex_material = material.xy;
+
+    world_position = (model * vec4(position, 1.0)).xyz;
+    // objects only have uniform scales, so the model matrix can also transform normals
+    world_normal = mat3(model) * normal;
+    gl_Position = matrix * vec4(position, 1.0);
+}
@@ -2,6 +2,9 @@
 
 pub mod single_colored;
 pub mod compute;
+pub mod debug;
+pub mod lit;
+pub mod textured;
 mod traits;
 
 pub use traits::UniformShader;
@@ -0,0 +1,19 @@
+#version 450
+
+#include <lighting.glsl>
+
+layout(set = 1, binding = 0) uniform sampler2D texture_sampler;
+
+layout(location = 0) in vec2 uv;
+layout(location = 1) in vec3 world_position;
+layout(location = 2) in vec3 world_normal;
+layout(location = 3) in vec2 vertex_material;
+layout(location = 4) in vec3 vertex_color;
+
+layout(location = 0) out vec4 fragment_color;
+
+void main() {
+  vec4 color = texture(texture_sampler, uv) * vec4(vertex_color, 1.0);
+  vec3 lit_color = apply_lighting(color.rgb, vertex_material, world_position, world_normal);
+  fragment_color = vec4(lit_color, color.a);
+}
@@ -0,0 +1,14 @@
+pub mod vs {
+  vulkano_shaders::shader! {
+      ty: "vertex",
+      path: "src/render/shaders/textured/vertex.glsl",
+  }
+}
+
+pub mod fs {
+  vulkano_shaders::shader! {
+      ty: "fragment",
+      path: "src/render/shaders/textured/fragment.glsl",
+      include: ["src/render/shaders/include"],
+  }
+}
@@ -0,0 +1,28 @@
+#version 450
+
+// vertex data
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+layout(location = 2) in vec2 uv;
+
+// instance data
+layout(location = 3) in mat4 matrix;
+layout(location = 7) in mat4 model;
+layout(location = 11) in vec4 color;
+layout(location = 12) in vec4 material;
+
+layout(location = 0) out vec2 fragment_uv;
+layout(location = 1) out vec3 world_position;
+layout(location = 2) out vec3 world_normal;
+layout(location = 3) out vec2 vertex_material;
+layout(location = 4) out vec3 vertex_color;
+
+void main() {
+    fragment_uv = uv;
+    vertex_material = material.xy;
+    vertex_color = color.rgb;
+    world_position = (model * vec4(position, 1.0)).xyz;
+    // objects only have uniform scales, so the model matrix can also transform normals
+    world_normal = mat3(model) * normal;
+    gl_Position = matrix * vec4(position, 1.0);
+}
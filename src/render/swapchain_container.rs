@@ -1,22 +1,24 @@
-use crate::render::vulkano_objects;
+use crate::render::{vulkano_objects, RenderError};
 use std::sync::Arc;
 use vulkano::{
   device::{physical::PhysicalDevice, Device},
   format::Format,
-  image::{traits::ImageAccess, AttachmentImage, ImageDimensions, SwapchainImage},
+  image::{traits::ImageAccess, SwapchainImage},
   render_pass::{Framebuffer, RenderPass},
   swapchain::{
-    self, AcquireError, Surface, Swapchain, SwapchainAcquireFuture, SwapchainCreateInfo,
-    SwapchainCreationError,
+    self, AcquireError, ColorSpace, PresentMode, Surface, Swapchain, SwapchainAcquireFuture,
+    SwapchainCreateInfo, SwapchainCreationError,
   },
 };
 use winit::window::Window;
 
 /// Manages swapchain related objects
 pub struct SwapchainContainer {
-  swapchain: Arc<Swapchain<Window>>,
-  swapchain_images: Vec<Arc<SwapchainImage<winit::window::Window>>>,
-  depth_image: Arc<AttachmentImage>,
+  swapchain: Arc<Swapchain<Arc<Window>>>,
+  swapchain_images: Vec<Arc<SwapchainImage<Arc<Window>>>>,
+  depth_format: Format,
+  /// Number of samples in each pixel, 1 if not multisampling
+  samples: u32,
   render_pass: Arc<RenderPass>,
   framebuffers: Vec<Arc<Framebuffer>>,
 }
@@ -25,64 +27,105 @@ impl SwapchainContainer {
   pub fn new(
     physical_device: PhysicalDevice,
     device: Arc<Device>,
-    surface: Arc<Surface<Window>>,
-  ) -> Self {
-    let (swapchain, swapchain_images) =
-      vulkano_objects::swapchain::create(&physical_device, device.clone(), surface);
+    surface: Arc<Surface<Arc<Window>>>,
+    present_mode: PresentMode,
+    hdr: bool,
+    samples: u32,
+  ) -> Result<Self, RenderError> {
+    let (swapchain, swapchain_images) = vulkano_objects::swapchain::create(
+      &physical_device,
+      device.clone(),
+      surface,
+      present_mode,
+      hdr,
+    )?;
 
-    let depth_image = AttachmentImage::transient_input_attachment(
+    let depth_format = vulkano_objects::render_pass::choose_depth_format(physical_device);
+    let shared_attachments = vulkano_objects::framebuffers::create_shared_attachments(
       device.clone(),
-      get_2d_image_dimensions(&swapchain_images[0]),
-      Format::D32_SFLOAT, // todo: search for available depth format
-    )
-    .unwrap();
+      swapchain_images[0].dimensions().width_height(),
+      swapchain.image_format(),
+      depth_format,
+      samples,
+    )?;
 
     let render_pass =
-      vulkano_objects::render_pass::create(device, swapchain.clone(), depth_image.clone());
+      vulkano_objects::render_pass::create(device, swapchain.image_format(), depth_format, samples);
     let framebuffers = vulkano_objects::framebuffers::create(
       render_pass.clone(),
       &swapchain_images,
-      depth_image.clone(),
+      &shared_attachments,
     );
 
-    Self {
+    Ok(Self {
       swapchain,
       swapchain_images,
-      depth_image,
+      depth_format,
+      samples,
       render_pass,
       framebuffers,
-    }
+    })
+  }
+
+  pub fn recreate_swapchain(
+    &mut self,
+    device: Arc<Device>,
+    surface: Arc<Surface<Arc<Window>>>,
+  ) -> Result<(), RenderError> {
+    self.recreate(device, surface, self.present_mode())
+  }
+
+  /// Recreates the swapchain with a present mode that should already be supported,
+  /// see [`vulkano_objects::swapchain::choose_present_mode`]
+  pub fn set_present_mode(
+    &mut self,
+    device: Arc<Device>,
+    surface: Arc<Surface<Arc<Window>>>,
+    present_mode: PresentMode,
+  ) -> Result<(), RenderError> {
+    self.recreate(device, surface, present_mode)
   }
 
-  pub fn recreate_swapchain(&mut self, device: Arc<Device>, surface: Arc<Surface<Window>>) {
+  fn recreate(
+    &mut self,
+    device: Arc<Device>,
+    surface: Arc<Surface<Arc<Window>>>,
+    present_mode: PresentMode,
+  ) -> Result<(), RenderError> {
     let (new_swapchain, new_swapchain_images) = match self.swapchain.recreate(SwapchainCreateInfo {
       image_extent: surface.window().inner_size().into(),
+      present_mode,
       ..self.swapchain.create_info()
     }) {
       Ok(r) => r,
-      Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return,
-      Err(e) => panic!("Failed to recreate swapchain: {:?}", e),
+      // happens while the window is being resized, it will be recreated again later
+      Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return Ok(()),
+      Err(e) => return Err(e.into()),
     };
 
     self.swapchain = new_swapchain;
 
-    self.depth_image = AttachmentImage::transient_input_attachment(
+    // depth and multisampled images have to match the new size
+    let shared_attachments = vulkano_objects::framebuffers::create_shared_attachments(
       device,
-      get_2d_image_dimensions(&new_swapchain_images[0]),
-      Format::D32_SFLOAT,
-    )
-    .unwrap();
+      new_swapchain_images[0].dimensions().width_height(),
+      self.swapchain.image_format(),
+      self.depth_format,
+      self.samples,
+    )?;
 
     self.framebuffers = vulkano_objects::framebuffers::create(
       self.render_pass.clone(),
       &new_swapchain_images,
-      self.depth_image.clone(),
+      &shared_attachments,
     );
+
+    Ok(())
   }
 
   pub fn acquire_next_swapchain_image(
     &self,
-  ) -> Result<(usize, bool, SwapchainAcquireFuture<Window>), AcquireError> {
+  ) -> Result<(usize, bool, SwapchainAcquireFuture<Arc<Window>>), AcquireError> {
     swapchain::acquire_next_image(self.swapchain.clone(), None)
   }
 
@@ -94,19 +137,23 @@ impl SwapchainContainer {
     &self.framebuffers
   }
 
-  pub fn get_swapchain(&self) -> Arc<Swapchain<Window>> {
+  pub fn get_swapchain(&self) -> Arc<Swapchain<Arc<Window>>> {
     self.swapchain.clone()
   }
 
-  pub fn image_count(&self) -> usize {
-    self.swapchain_images.len()
+  pub fn samples(&self) -> u32 {
+    self.samples
   }
-}
 
-fn get_2d_image_dimensions(image: &dyn ImageAccess) -> [u32; 2] {
-  if let ImageDimensions::Dim2d { width, height, .. } = image.dimensions() {
-    [width, height]
-  } else {
-    panic!()
+  pub fn present_mode(&self) -> PresentMode {
+    self.swapchain.create_info().present_mode
+  }
+
+  pub fn surface_format(&self) -> (Format, ColorSpace) {
+    let create_info = self.swapchain.create_info();
+    (
+      create_info.image_format.unwrap(),
+      create_info.image_color_space,
+    )
   }
 }
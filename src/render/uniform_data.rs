@@ -0,0 +1,76 @@
+use crate::game_objects::{Lighting, MAX_POINT_LIGHTS};
+use bytemuck::{Pod, Zeroable};
+
+/// Single light, as laid out in the lit fragment shader (std140)
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
+pub struct LightData {
+  /// Direction for directional lights, position for point lights
+  pub position: [f32; 4],
+  pub color: [f32; 4],
+  /// Ambient, diffuse, specular and attenuation
+  pub intensity: [f32; 4],
+}
+
+/// Contents of the lighting uniform buffer
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
+pub struct LightingData {
+  pub directional: LightData,
+  pub point_lights: [LightData; MAX_POINT_LIGHTS],
+  /// Only x is used, the others exist for alignment
+  pub point_light_count: [u32; 4],
+}
+
+impl From<&Lighting> for LightingData {
+  fn from(lighting: &Lighting) -> Self {
+    let directional = &lighting.directional;
+    let [x, y, z] = directional.direction;
+
+    let mut data = LightingData {
+      directional: LightData {
+        position: [x, y, z, 0.0],
+        color: extend(directional.color),
+        intensity: [
+          directional.ambient,
+          directional.diffuse,
+          directional.specular,
+          0.0,
+        ],
+      },
+      ..Default::default()
+    };
+
+    let point_lights = lighting.point_lights.iter().take(MAX_POINT_LIGHTS);
+    data.point_light_count[0] = point_lights.len() as u32;
+    for (slot, light) in data.point_lights.iter_mut().zip(point_lights) {
+      let [x, y, z] = light.position;
+      *slot = LightData {
+        position: [x, y, z, 1.0],
+        color: extend(light.color),
+        intensity: [
+          light.ambient,
+          light.diffuse,
+          light.specular,
+          light.attenuation,
+        ],
+      };
+    }
+
+    data
+  }
+}
+
+fn extend([r, g, b]: [f32; 3]) -> [f32; 4] {
+  [r, g, b, 1.0]
+}
+
+/// Instance range and bounding sphere of a single model, read by the instance compute shader
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
+pub struct ModelData {
+  pub first_instance: u32,
+  pub instance_count: u32,
+  /// Radius of a sphere centered at the model origin that contains every vertex
+  pub bounding_radius: f32,
+}
@@ -1,17 +1,43 @@
+use crate::game_objects::Material;
 use bytemuck::{Pod, Zeroable};
+use cgmath::Matrix4;
 use vulkano::impl_vertex;
 
 #[repr(C)]
 #[derive(Default, Debug, Copy, Clone, Zeroable, Pod)]
 pub struct Vertex3d {
   pub position: [f32; 3],
+  pub normal: [f32; 3],
+  /// Texture coordinates, where (0, 0) is the top left corner of the image
+  pub uv: [f32; 2],
 }
 
-impl_vertex!(Vertex3d, position);
+impl_vertex!(Vertex3d, position, normal, uv);
 
+/// Instance data, used both as the compute shader input (where `matrix` is not set)
+/// and as its output
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
 pub struct MatrixInstance {
+  /// Projection-view-model matrix
   pub matrix: [[f32; 4]; 4],
+  pub model: [[f32; 4]; 4],
+  /// RGBA, where alpha is always 1 for now
+  pub color: [f32; 4],
+  /// Specular multiplier and shininess. The compute shader writes the index of the object
+  /// into the third component, which is exact for up to 2^24 objects.
+  pub material: [f32; 4],
+}
+impl_vertex!(MatrixInstance, matrix, model, color, material);
+
+impl MatrixInstance {
+  pub fn new(model: Matrix4<f32>, material: Material) -> Self {
+    let [r, g, b] = material.color;
+    Self {
+      model: model.into(),
+      color: [r, g, b, 1.0],
+      material: [material.specular, material.shininess, 0.0, 0.0],
+      ..Default::default()
+    }
+  }
 }
-impl_vertex!(MatrixInstance, matrix);
@@ -1,10 +1,16 @@
-use crate::render::{models::Model, vulkano_objects::QueueFamilies};
-use bytemuck::Pod;
+use crate::render::{
+  models::Model,
+  uniform_data::{LightingData, ModelData},
+  vulkano_objects::{QueueFamilies, Queues},
+  RenderError,
+};
+use bytemuck::{Pod, Zeroable};
 use std::sync::Arc;
 use vulkano::{
   buffer::{BufferContents, BufferUsage, CpuAccessibleBuffer, DeviceLocalBuffer, ImmutableBuffer},
-  command_buffer::{CommandBufferExecFuture, PrimaryAutoCommandBuffer},
-  device::{Device, Queue},
+  command_buffer::{CommandBufferExecFuture, DrawIndexedIndirectCommand, PrimaryAutoCommandBuffer},
+  device::{physical::QueueFamily, Device, Queue},
+  memory::DeviceMemoryAllocationError,
   sync::{GpuFuture, NowFuture},
 };
 
@@ -12,25 +18,38 @@ use vulkano::{
 pub struct MainBuffers<V: BufferContents + Pod, I: BufferContents + Pod> {
   pub vertex: Arc<ImmutableBuffer<[V]>>,
   pub index: Arc<ImmutableBuffer<[u16]>>,
-  pub instance: Arc<DeviceLocalBuffer<[I]>>,
+  /// Written by the instance compute shader, one for each frame in flight
+  pub frames: Vec<FrameBuffers<I>>,
   pub model_lengths: Vec<(u32, i32)>,
 }
 
+/// Buffers written by the instance compute shader and read by the main command buffers.
+///
+/// Each frame in flight has its own, so that computing the next frame doesn't overwrite data
+/// that is still being drawn.
+pub struct FrameBuffers<I: BufferContents + Pod> {
+  pub instance: Arc<DeviceLocalBuffer<[I]>>,
+  /// Camera position, read when lighting
+  pub frame_data: Arc<DeviceLocalBuffer<[f32; 4]>>,
+  /// One for each model, instance counts get written by the instance compute shader
+  pub draw_commands: Arc<DeviceLocalBuffer<[DrawIndexedIndirectCommand]>>,
+}
+
 impl<V: BufferContents + Pod, I: BufferContents + Pod + Default> MainBuffers<V, I> {
   pub fn new(
     device: Arc<Device>,
     queue_families: &QueueFamilies,
     transfer_queue: Arc<Queue>,
     models: &Vec<Box<dyn Model<V>>>,
+    frames_in_flight: usize,
     max_instance_count: usize,
-  ) -> Self {
-    let (vertex, vertex_future) = create_immutable_vertex::<V>(transfer_queue.clone(), models);
-    let (index, index_future) = create_immutable_index::<V>(transfer_queue, models);
+  ) -> Result<Self, RenderError> {
+    let (vertex, vertex_future) = create_immutable_vertex::<V>(transfer_queue.clone(), models)?;
+    let (index, index_future) = create_immutable_index::<V>(transfer_queue, models)?;
 
     let fence = vertex_future
       .join(index_future)
-      .then_signal_fence_and_flush()
-      .unwrap();
+      .then_signal_fence_and_flush()?;
 
     let model_lengths = models
       .iter()
@@ -42,25 +61,55 @@ impl<V: BufferContents + Pod, I: BufferContents + Pod + Default> MainBuffers<V,
       })
       .collect();
 
-    let instance =
-      create_device_instance(device.clone(), max_instance_count as u64, queue_families);
+    let frames = (0..frames_in_flight)
+      .map(|_| -> Result<_, RenderError> {
+        Ok(FrameBuffers {
+          instance: create_device_instance(
+            device.clone(),
+            max_instance_count as u64,
+            [queue_families.compute, queue_families.graphics],
+          )?,
+          frame_data: create_device_frame_data(device.clone(), queue_families)?,
+          draw_commands: create_device_draw_commands(
+            device.clone(),
+            models.len() as u64,
+            queue_families,
+          )?,
+        })
+      })
+      .collect::<Result<_, _>>()?;
 
-    fence.wait(None).unwrap();
+    fence.wait(None)?;
 
-    Self {
+    Ok(Self {
       vertex,
       index,
-      instance,
+      frames,
       model_lengths,
-    }
+    })
   }
 }
 
 pub struct Buffers<V: BufferContents + Pod, I: BufferContents + Pod> {
   main: MainBuffers<V, I>,
 
-  /// Used in the compute shader in order to calculate instance matrices on the gpu
+  /// Used in the compute shader in order to calculate instance matrices on the gpu,
+  /// one for each frame in flight
   instance_source_models: Vec<Arc<CpuAccessibleBuffer<[I]>>>,
+
+  /// Lights used in the fragment shader, one for each frame in flight
+  lighting: Vec<Arc<CpuAccessibleBuffer<LightingData>>>,
+
+  /// Instance ranges and bounding spheres used for culling in the compute shader,
+  /// one for each frame in flight
+  model_data: Vec<Arc<CpuAccessibleBuffer<[ModelData]>>>,
+
+  /// Draw commands with no instances, copied into the main ones before culling.
+  /// One for each frame in flight, as their offsets change together with the number of objects.
+  draw_command_templates: Vec<Arc<CpuAccessibleBuffer<[DrawIndexedIndirectCommand]>>>,
+
+  /// Number of instances that the instance buffers can hold
+  instance_capacity: usize,
 }
 
 impl<V: BufferContents + Pod, I: BufferContents + Pod + Default> Buffers<V, I> {
@@ -68,34 +117,125 @@ impl<V: BufferContents + Pod, I: BufferContents + Pod + Default> Buffers<V, I> {
     device: Arc<Device>,
     queue_families: &QueueFamilies,
     transfer_queue: Arc<Queue>,
-    buffer_count: usize,
+    frames_in_flight: usize,
     models: &Vec<Box<dyn Model<V>>>,
     max_instance_count: usize,
-  ) -> Self {
-    let instance_source_models =
-      vec![
-        create_cpu_accessible_instance_source_models(device.clone(), max_instance_count);
-        buffer_count
-      ];
-
-    Self {
-      main: MainBuffers::new(
-        device,
-        queue_families,
-        transfer_queue,
-        models,
-        max_instance_count,
-      ),
+  ) -> Result<Self, RenderError> {
+    let instance_source_models = (0..frames_in_flight)
+      .map(|_| create_cpu_accessible_instance_source_models(device.clone(), max_instance_count))
+      .collect::<Result<_, _>>()?;
+    let lighting = (0..frames_in_flight)
+      .map(|_| create_cpu_accessible_lighting(device.clone()))
+      .collect::<Result<_, _>>()?;
+
+    let main = MainBuffers::new(
+      device.clone(),
+      queue_families,
+      transfer_queue,
+      models,
+      frames_in_flight,
+      max_instance_count,
+    )?;
+
+    let model_data = (0..frames_in_flight)
+      .map(|_| create_cpu_accessible_model_data(device.clone(), models.len()))
+      .collect::<Result<_, _>>()?;
+    let draw_command_templates = (0..frames_in_flight)
+      .map(|_| create_cpu_accessible_draw_command_template(device.clone(), models.len()))
+      .collect::<Result<_, _>>()?;
+
+    Ok(Self {
+      main,
       instance_source_models,
+      lighting,
+      model_data,
+      draw_command_templates,
+      instance_capacity: max_instance_count,
+    })
+  }
+
+  /// Reallocates instance buffers if they can't hold `instance_count` instances, returning true
+  /// if that happened. Their size at least doubles, so that spawning objects one by one doesn't
+  /// reallocate every time.
+  ///
+  /// New buffers are empty, so instance source models have to be updated again for every frame
+  /// in flight.
+  pub fn reserve_instances(
+    &mut self,
+    device: Arc<Device>,
+    queues: &Queues,
+    instance_count: usize,
+  ) -> Result<bool, RenderError> {
+    if instance_count <= self.instance_capacity {
+      return Ok(false);
     }
+
+    let capacity = instance_count.max(self.instance_capacity * 2);
+    for frame in self.main.frames.iter_mut() {
+      frame.instance = create_device_instance(
+        device.clone(),
+        capacity as u64,
+        [queues.compute.family(), queues.graphics.family()],
+      )?;
+    }
+    self.instance_source_models = (0..self.instance_source_models.len())
+      .map(|_| create_cpu_accessible_instance_source_models(device.clone(), capacity))
+      .collect::<Result<_, _>>()?;
+    self.instance_capacity = capacity;
+
+    Ok(true)
   }
 
-  pub fn update_instance_source_models(&mut self, buffer_i: usize, data: Vec<I>) {
-    let mut content = self.instance_source_models[buffer_i]
-      .write()
-      .unwrap_or_else(|e| panic!("Failed to write to instance buffer\n{}", e));
+  pub fn update_instance_source_models(
+    &mut self,
+    buffer_i: usize,
+    data: Vec<I>,
+  ) -> Result<(), RenderError> {
+    let mut content = self.instance_source_models[buffer_i].write()?;
 
     content[0..data.len()].copy_from_slice(data.as_slice());
+    Ok(())
+  }
+
+  pub fn update_lighting(
+    &mut self,
+    buffer_i: usize,
+    data: LightingData,
+  ) -> Result<(), RenderError> {
+    let mut content = self.lighting[buffer_i].write()?;
+
+    *content = data;
+    Ok(())
+  }
+
+  /// Writes the instance ranges of every model, together with the draw commands that use them
+  pub fn update_model_data(
+    &mut self,
+    buffer_i: usize,
+    data: Vec<ModelData>,
+  ) -> Result<(), RenderError> {
+    let mut content = self.model_data[buffer_i].write()?;
+    content.copy_from_slice(data.as_slice());
+
+    let mut content = self.draw_command_templates[buffer_i].write()?;
+    let mut first_index = 0;
+    let mut vertex_offset = 0;
+    for ((command, &(index_len, vertex_len)), model) in content
+      .iter_mut()
+      .zip(self.main.model_lengths.iter())
+      .zip(data.iter())
+    {
+      *command = DrawIndexedIndirectCommand {
+        index_count: index_len,
+        instance_count: 0,
+        first_index,
+        vertex_offset: vertex_offset as u32,
+        first_instance: model.first_instance,
+      };
+      first_index += index_len;
+      vertex_offset += vertex_len;
+    }
+    Ok(())
   }
 
   pub fn get_main(&self) -> &MainBuffers<V, I> {
@@ -105,15 +245,33 @@ impl<V: BufferContents + Pod, I: BufferContents + Pod + Default> Buffers<V, I> {
   pub fn get_instance_source_model(&self, buffer_i: usize) -> Arc<CpuAccessibleBuffer<[I]>> {
     self.instance_source_models[buffer_i].clone()
   }
+
+  pub fn get_lighting(&self, buffer_i: usize) -> Arc<CpuAccessibleBuffer<LightingData>> {
+    self.lighting[buffer_i].clone()
+  }
+
+  pub fn get_model_data(&self, buffer_i: usize) -> Arc<CpuAccessibleBuffer<[ModelData]>> {
+    self.model_data[buffer_i].clone()
+  }
+
+  pub fn get_draw_command_template(
+    &self,
+    buffer_i: usize,
+  ) -> Arc<CpuAccessibleBuffer<[DrawIndexedIndirectCommand]>> {
+    self.draw_command_templates[buffer_i].clone()
+  }
 }
 
 fn create_immutable_vertex<V>(
   queue: Arc<Queue>,
   models: &Vec<Box<dyn Model<V>>>,
-) -> (
-  Arc<ImmutableBuffer<[V]>>,
-  CommandBufferExecFuture<NowFuture, PrimaryAutoCommandBuffer>,
-)
+) -> Result<
+  (
+    Arc<ImmutableBuffer<[V]>>,
+    CommandBufferExecFuture<NowFuture, PrimaryAutoCommandBuffer>,
+  ),
+  DeviceMemoryAllocationError,
+>
 where
   V: BufferContents + Pod,
 {
@@ -122,16 +280,19 @@ where
     .map(|m| m.get_vertices().clone())
     .flatten()
     .collect();
-  ImmutableBuffer::from_iter(vertices.into_iter(), BufferUsage::vertex_buffer(), queue).unwrap()
+  ImmutableBuffer::from_iter(vertices.into_iter(), BufferUsage::vertex_buffer(), queue)
 }
 
 fn create_immutable_index<V>(
   queue: Arc<Queue>,
   models: &Vec<Box<dyn Model<V>>>,
-) -> (
-  Arc<ImmutableBuffer<[u16]>>,
-  CommandBufferExecFuture<NowFuture, PrimaryAutoCommandBuffer>,
-)
+) -> Result<
+  (
+    Arc<ImmutableBuffer<[u16]>>,
+    CommandBufferExecFuture<NowFuture, PrimaryAutoCommandBuffer>,
+  ),
+  DeviceMemoryAllocationError,
+>
 where
   V: BufferContents,
 {
@@ -140,14 +301,15 @@ where
     .map(|m| m.get_indices().clone())
     .flatten()
     .collect();
-  ImmutableBuffer::from_iter(indices.into_iter(), BufferUsage::index_buffer(), queue).unwrap()
+  ImmutableBuffer::from_iter(indices.into_iter(), BufferUsage::index_buffer(), queue)
 }
 
+/// Written by the compute shader and read as a vertex buffer by the main command buffers
 fn create_device_instance<'a, I>(
   device: Arc<Device>,
   max_total_instances: u64,
-  queue_families: &QueueFamilies,
-) -> Arc<DeviceLocalBuffer<[I]>>
+  queue_families: [QueueFamily<'a>; 2],
+) -> Result<Arc<DeviceLocalBuffer<[I]>>, DeviceMemoryAllocationError>
 where
   I: BufferContents + Pod + Default,
 {
@@ -159,15 +321,81 @@ where
       vertex_buffer: true,
       ..BufferUsage::none()
     },
-    [queue_families.compute, queue_families.transfers],
+    queue_families,
+  )
+}
+
+fn create_device_frame_data(
+  device: Arc<Device>,
+  queue_families: &QueueFamilies,
+) -> Result<Arc<DeviceLocalBuffer<[f32; 4]>>, DeviceMemoryAllocationError> {
+  DeviceLocalBuffer::new(
+    device,
+    BufferUsage {
+      storage_buffer: true,
+      ..BufferUsage::none()
+    },
+    [queue_families.compute, queue_families.graphics],
+  )
+}
+
+fn create_device_draw_commands(
+  device: Arc<Device>,
+  model_count: u64,
+  queue_families: &QueueFamilies,
+) -> Result<Arc<DeviceLocalBuffer<[DrawIndexedIndirectCommand]>>, DeviceMemoryAllocationError> {
+  DeviceLocalBuffer::array(
+    device,
+    model_count,
+    BufferUsage {
+      storage_buffer: true,
+      indirect_buffer: true,
+      transfer_destination: true,
+      ..BufferUsage::none()
+    },
+    [queue_families.compute, queue_families.graphics],
+  )
+}
+
+fn create_cpu_accessible_model_data(
+  device: Arc<Device>,
+  model_count: usize,
+) -> Result<Arc<CpuAccessibleBuffer<[ModelData]>>, DeviceMemoryAllocationError> {
+  CpuAccessibleBuffer::from_iter(
+    device,
+    BufferUsage::storage_buffer(),
+    false,
+    vec![ModelData::default(); model_count].into_iter(),
+  )
+}
+
+fn create_cpu_accessible_draw_command_template(
+  device: Arc<Device>,
+  model_count: usize,
+) -> Result<Arc<CpuAccessibleBuffer<[DrawIndexedIndirectCommand]>>, DeviceMemoryAllocationError> {
+  CpuAccessibleBuffer::from_iter(
+    device,
+    BufferUsage::transfer_source(),
+    false,
+    vec![DrawIndexedIndirectCommand::zeroed(); model_count].into_iter(),
+  )
+}
+
+fn create_cpu_accessible_lighting(
+  device: Arc<Device>,
+) -> Result<Arc<CpuAccessibleBuffer<LightingData>>, DeviceMemoryAllocationError> {
+  CpuAccessibleBuffer::from_data(
+    device,
+    BufferUsage::uniform_buffer(),
+    false,
+    LightingData::default(),
   )
-  .unwrap()
 }
 
 fn create_cpu_accessible_instance_source_models<I>(
   device: Arc<Device>,
   max_total_instances: usize,
-) -> Arc<CpuAccessibleBuffer<[I]>>
+) -> Result<Arc<CpuAccessibleBuffer<[I]>>, DeviceMemoryAllocationError>
 where
   I: BufferContents + Pod + Default,
 {
@@ -182,5 +410,4 @@ where
     false,
     data.into_iter(),
   )
-  .unwrap()
 }
@@ -1,9 +1,14 @@
-use crate::render::vulkano_objects::buffers::Buffers;
+use crate::render::{
+  graphics_pipelines::{uses_textures, GraphicsPipelines, ViewMode},
+  vulkano_objects::{buffers::Buffers, render_pass, textures::Textures},
+  RenderError,
+};
 use bytemuck::Pod;
 use std::sync::Arc;
 use vulkano::{
-  buffer::{BufferContents, TypedBufferAccess},
+  buffer::{BufferContents, BufferSlice, TypedBufferAccess},
   descriptor_set::PersistentDescriptorSet,
+  image::ImageAccess,
   pipeline::{ComputePipeline, Pipeline, PipelineBindPoint},
   DeviceSize,
 };
@@ -13,76 +18,107 @@ use vulkano::{
     AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, SubpassContents,
   },
   device::{Device, Queue},
-  format::ClearValue,
-  pipeline::GraphicsPipeline,
   render_pass::Framebuffer,
 };
 
+/// Draws every model with the pipeline that matches it, see [`GraphicsPipelines`].
+///
+/// Each model has a single draw command, where the instance count gets written by the instance
+/// compute shader after culling. Models are grouped by texture, so that each texture descriptor
+/// set only gets bound once.
+///
+/// Returns a command buffer for each framebuffer, all using the buffers and lighting descriptor
+/// set of the frame in flight `frame_i`.
+/// The view mode has to be available, see [`GraphicsPipelines::available_view_modes`].
+#[allow(clippy::too_many_arguments)]
 pub fn create_main<V: BufferContents + Pod, I: BufferContents + Pod + Default>(
   device: Arc<Device>,
   graphics_queue: Arc<Queue>,
-  pipeline: Arc<GraphicsPipeline>,
+  pipelines: &GraphicsPipelines,
+  view_mode: ViewMode,
   framebuffers: &Vec<Arc<Framebuffer>>,
   buffers: &Buffers<V, I>,
-  instance_count_per_model: &Vec<u32>,
-) -> Vec<Arc<PrimaryAutoCommandBuffer>> {
-  framebuffers
+  frame_i: usize,
+  descriptor_set: Arc<PersistentDescriptorSet>,
+  textures: &Textures,
+) -> Result<Vec<Arc<PrimaryAutoCommandBuffer>>, RenderError> {
+  let main_buffers = buffers.get_main();
+  let frame_buffers = &main_buffers.frames[frame_i];
+  let [untextured_pipeline, textured_pipeline] =
+    pipelines.get(view_mode).expect("view mode isn't available");
+
+  // draw commands can be used in any order, as they contain their own offsets
+  let mut draws: Vec<(Option<usize>, u64)> = textures
+    .model_groups
     .iter()
     .enumerate()
-    .map(|(_, framebuffer)| {
-      let main_buffers = buffers.get_main();
+    .map(|(model_i, &group)| (group, model_i as u64))
+    .collect();
+  draws.sort_by_key(|&(group, _)| group);
 
+  framebuffers
+    .iter()
+    .map(|framebuffer| {
       let mut builder = AutoCommandBufferBuilder::primary(
         device.clone(),
         graphics_queue.family(),
         CommandBufferUsage::MultipleSubmit,
-      )
-      .unwrap();
+      )?;
 
-      builder
-        .begin_render_pass(
-          framebuffer.clone(),
-          SubpassContents::Inline,
-          vec![[0.1, 0.1, 0.1, 1.0].into(), ClearValue::Depth(1.0)],
-        )
-        .unwrap()
-        .bind_pipeline_graphics(pipeline.clone());
+      builder.begin_render_pass(
+        framebuffer.clone(),
+        SubpassContents::Inline,
+        render_pass::clear_values(framebuffer, pipelines.reverse_z),
+      )?;
+
+      let draw_commands =
+        BufferSlice::from_typed_buffer_access(frame_buffers.draw_commands.clone());
 
       // bind index and vertex buffers
       builder
         .bind_vertex_buffers(
           0,
-          (main_buffers.vertex.clone(), main_buffers.instance.clone()),
+          (main_buffers.vertex.clone(), frame_buffers.instance.clone()),
         )
         .bind_index_buffer(main_buffers.index.clone());
 
-      // draw with offsets
-      let mut index_offset = 0;
-      let mut vertex_offset = 0;
-      let mut instance_offset = 0;
-      for (&(index_len, vertex_len), &instance_count) in main_buffers
-        .model_lengths
-        .iter()
-        .zip(instance_count_per_model.iter())
-      {
-        builder
-          .draw_indexed(
-            index_len,
-            instance_count,
-            index_offset,
-            vertex_offset,
-            instance_offset,
-          )
-          .unwrap();
-
-        index_offset += index_len;
-        vertex_offset += vertex_len;
-        instance_offset += instance_count;
+      // switch pipelines and textures only between groups
+      let mut bound_group = None;
+      for &(group, model_i) in draws.iter() {
+        if bound_group != Some(group) {
+          let pipeline = match group {
+            Some(_) => textured_pipeline.clone(),
+            None => untextured_pipeline.clone(),
+          };
+
+          builder
+            .bind_pipeline_graphics(pipeline.clone())
+            .bind_descriptor_sets(
+              PipelineBindPoint::Graphics,
+              pipeline.layout().clone(),
+              0,
+              descriptor_set.clone(),
+            );
+          if let Some(group) = group.filter(|_| uses_textures(&pipeline)) {
+            builder.bind_descriptor_sets(
+              PipelineBindPoint::Graphics,
+              pipeline.layout().clone(),
+              1,
+              textures.descriptor_sets[group].clone(),
+            );
+          }
+
+          bound_group = Some(group);
+        }
+
+        // there is a draw command for each model
+        let command = draw_commands.slice(model_i..(model_i + 1)).unwrap();
+        builder.draw_indexed_indirect(command)?;
       }
 
-      builder.end_render_pass().unwrap();
+      builder.end_render_pass()?;
 
-      Arc::new(builder.build().unwrap())
+      Ok(Arc::new(builder.build()?))
     })
     .collect()
 }
@@ -93,7 +129,7 @@ pub fn create_copy<T, S, D>(
   transfers_queue: Arc<Queue>,
   source: Arc<S>,
   destination: Arc<D>,
-) -> Arc<PrimaryAutoCommandBuffer>
+) -> Result<Arc<PrimaryAutoCommandBuffer>, RenderError>
 where
   S: TypedBufferAccess<Content = T> + 'static,
   D: TypedBufferAccess<Content = T> + 'static,
@@ -102,12 +138,11 @@ where
     device.clone(),
     transfers_queue.family(),
     CommandBufferUsage::MultipleSubmit,
-  )
-  .unwrap();
+  )?;
 
-  builder.copy_buffer(source, destination).unwrap();
+  builder.copy_buffer(source, destination)?;
 
-  Arc::new(builder.build().unwrap())
+  Ok(Arc::new(builder.build()?))
 }
 
 #[allow(dead_code)]
@@ -119,7 +154,7 @@ pub fn create_slice_copy<T, S, D>(
   destination: Arc<D>,
   destination_offset: DeviceSize,
   count: DeviceSize,
-) -> Arc<PrimaryAutoCommandBuffer>
+) -> Result<Arc<PrimaryAutoCommandBuffer>, RenderError>
 where
   S: TypedBufferAccess<Content = [T]> + 'static,
   D: TypedBufferAccess<Content = [T]> + 'static,
@@ -128,37 +163,40 @@ where
     device.clone(),
     transfers_queue.family(),
     CommandBufferUsage::MultipleSubmit,
-  )
-  .unwrap();
+  )?;
 
-  builder
-    .copy_buffer_dimensions(
-      source,
-      source_offset,
-      destination,
-      destination_offset,
-      count,
-    )
-    .unwrap();
+  builder.copy_buffer_dimensions(
+    source,
+    source_offset,
+    destination,
+    destination_offset,
+    count,
+  )?;
 
-  Arc::new(builder.build().unwrap())
+  Ok(Arc::new(builder.build()?))
 }
 
+/// Resets the draw commands and then culls and transforms every instance
 pub fn create_instance_compute<V: BufferContents + Pod, I: BufferContents + Pod + Default, Pc>(
   device: Arc<Device>,
   compute_queue: Arc<Queue>,
   compute_pipeline: Arc<ComputePipeline>,
+  buffers: &Buffers<V, I>,
+  buffer_i: usize,
   descriptor_set: Arc<PersistentDescriptorSet>,
   push_constants: Pc,
   instance_count: usize,
-) -> Arc<PrimaryAutoCommandBuffer> {
+) -> Result<Arc<PrimaryAutoCommandBuffer>, RenderError> {
   let mut builder = AutoCommandBufferBuilder::primary(
     device.clone(),
     compute_queue.family(),
     CommandBufferUsage::OneTimeSubmit,
-  )
-  .unwrap();
+  )?;
   builder
+    .copy_buffer(
+      buffers.get_draw_command_template(buffer_i),
+      buffers.get_main().frames[buffer_i].draw_commands.clone(),
+    )?
     .bind_pipeline_compute(compute_pipeline.clone())
     .push_constants(compute_pipeline.layout().clone(), 0, push_constants)
     .bind_descriptor_sets(
@@ -167,8 +205,30 @@ pub fn create_instance_compute<V: BufferContents + Pod, I: BufferContents + Pod
       0,
       descriptor_set,
     )
-    .dispatch([(instance_count / 64 + 1) as u32, 1, 1])
-    .unwrap();
+    .dispatch([(instance_count / 64 + 1) as u32, 1, 1])?;
   // Finish building the command buffer by calling `build`.
-  Arc::new(builder.build().unwrap())
+  Ok(Arc::new(builder.build()?))
+}
+
+/// Copies the whole image into a buffer, used to read offscreen frames on the cpu
+pub fn create_image_to_buffer_copy<Px, S, D>(
+  device: Arc<Device>,
+  queue: Arc<Queue>,
+  image: Arc<S>,
+  buffer: Arc<D>,
+) -> Result<Arc<PrimaryAutoCommandBuffer>, RenderError>
+where
+  S: ImageAccess + 'static,
+  D: TypedBufferAccess<Content = [Px]> + 'static,
+  Px: Send + Sync + 'static,
+{
+  let mut builder = AutoCommandBufferBuilder::primary(
+    device.clone(),
+    queue.family(),
+    CommandBufferUsage::MultipleSubmit,
+  )?;
+
+  builder.copy_image_to_buffer(image, buffer)?;
+
+  Ok(Arc::new(builder.build()?))
 }
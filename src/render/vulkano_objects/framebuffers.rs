@@ -1,5 +1,11 @@
+use crate::render::RenderError;
 use std::sync::Arc;
-use vulkano::{image::AttachmentImage, render_pass::FramebufferCreateInfo};
+use vulkano::{
+  device::Device,
+  format::Format,
+  image::{view::ImageViewAbstract, AttachmentImage, SampleCount},
+  render_pass::FramebufferCreateInfo,
+};
 
 use vulkano::{
   image::{view::ImageView, SwapchainImage},
@@ -7,20 +13,50 @@ use vulkano::{
 };
 use winit::window::Window;
 
+/// Creates the attachments that come after the color one and are shared between framebuffers:
+/// the depth image and, with more than one sample, the color image that gets resolved into
+/// the first attachment
+pub fn create_shared_attachments(
+  device: Arc<Device>,
+  dimensions: [u32; 2],
+  color_format: Format,
+  depth_format: Format,
+  samples: u32,
+) -> Result<Vec<Arc<dyn ImageViewAbstract>>, RenderError> {
+  if samples == 1 {
+    let depth_image =
+      AttachmentImage::transient_input_attachment(device, dimensions, depth_format)?;
+    return Ok(vec![ImageView::new_default(depth_image).unwrap()]);
+  }
+
+  let samples = SampleCount::try_from(samples).unwrap();
+  let depth_image =
+    AttachmentImage::transient_multisampled(device.clone(), dimensions, samples, depth_format)?;
+  let color_image =
+    AttachmentImage::transient_multisampled(device, dimensions, samples, color_format)?;
+
+  Ok(vec![
+    ImageView::new_default(depth_image).unwrap(),
+    ImageView::new_default(color_image).unwrap(),
+  ])
+}
+
 pub fn create(
   render_pass: Arc<RenderPass>,
-  swapchain_images: &[Arc<SwapchainImage<Window>>],
-  depth_image: Arc<AttachmentImage>,
+  swapchain_images: &[Arc<SwapchainImage<Arc<Window>>>],
+  shared_attachments: &[Arc<dyn ImageViewAbstract>],
 ) -> Vec<Arc<Framebuffer>> {
-  let depth_view = ImageView::new_default(depth_image).unwrap();
   swapchain_images
     .iter()
     .map(|image| {
-      let view = ImageView::new_default(image.clone()).unwrap();
+      let view: Arc<dyn ImageViewAbstract> = ImageView::new_default(image.clone()).unwrap();
       Framebuffer::new(
         render_pass.clone(),
         FramebufferCreateInfo {
-          attachments: vec![view, depth_view.clone()],
+          attachments: [view]
+            .into_iter()
+            .chain(shared_attachments.iter().cloned())
+            .collect(),
           ..Default::default()
         },
       )
@@ -28,3 +64,23 @@ pub fn create(
     })
     .collect::<Vec<_>>()
 }
+
+/// Creates a single framebuffer that draws into an offscreen color image
+pub fn create_offscreen(
+  render_pass: Arc<RenderPass>,
+  color_image: Arc<AttachmentImage>,
+  shared_attachments: &[Arc<dyn ImageViewAbstract>],
+) -> Arc<Framebuffer> {
+  let view: Arc<dyn ImageViewAbstract> = ImageView::new_default(color_image).unwrap();
+  Framebuffer::new(
+    render_pass,
+    FramebufferCreateInfo {
+      attachments: [view]
+        .into_iter()
+        .chain(shared_attachments.iter().cloned())
+        .collect(),
+      ..Default::default()
+    },
+  )
+  .unwrap()
+}
@@ -1,30 +1,114 @@
+use crate::render::RenderError;
+use log::{debug, error, info, warn};
 use std::sync::Arc;
-use vulkano::instance::{Instance, InstanceCreateInfo};
+use vulkano::instance::{
+  debug::{DebugCallback, Message, MessageSeverity, MessageType},
+  Instance, InstanceCreateInfo, InstanceExtensions,
+};
 
-const LIST_AVAILABLE_LAYERS: bool = false;
-const ENABLE_VALIDATION_LAYERS: bool = false;
-const VALIDATION_LAYERS: &[&str] = &["VK_LAYER_LUNARG_api_dump"];
+const VALIDATION_LAYER: &str = "VK_LAYER_KHRONOS_validation";
 
-pub fn create() -> Arc<Instance> {
-  let required_extensions = vulkano_win::required_extensions();
+/// Creates an instance with the extensions required for drawing to a window.
+///
+/// With `validation`, also returns the callback that logs validation messages,
+/// which only receives them while it's kept alive.
+pub fn create(validation: bool) -> Result<(Arc<Instance>, Option<DebugCallback>), RenderError> {
+  // exposes HDR color spaces, only enabled when available
+  let optional_extensions = InstanceExtensions {
+    ext_swapchain_colorspace: true,
+    ..InstanceExtensions::none()
+  };
+  // optional extensions are skipped if they can't be listed
+  let supported_extensions =
+    InstanceExtensions::supported_by_core().unwrap_or_else(|_| InstanceExtensions::none());
 
-  if LIST_AVAILABLE_LAYERS {
-    let layers: Vec<_> = vulkano::instance::layers_list().unwrap().collect();
-    let layer_names = layers.iter().map(|l| l.name());
-    println!(
-      "Available layers:\n {:?}",
-      layer_names.clone().collect::<Vec<&str>>()
-    );
-  }
+  create_with_extensions(
+    vulkano_win::required_extensions()
+      .union(&supported_extensions.intersection(&optional_extensions)),
+    validation,
+  )
+}
+
+/// Creates an instance without any surface extensions, used when rendering offscreen
+pub fn create_headless(
+  validation: bool,
+) -> Result<(Arc<Instance>, Option<DebugCallback>), RenderError> {
+  create_with_extensions(InstanceExtensions::none(), validation)
+}
+
+fn create_with_extensions(
+  required_extensions: InstanceExtensions,
+  validation: bool,
+) -> Result<(Arc<Instance>, Option<DebugCallback>), RenderError> {
+  let validation = validation && validation_layer_available();
 
   let mut create_info = InstanceCreateInfo {
     enabled_extensions: required_extensions,
     ..Default::default()
   };
 
-  if ENABLE_VALIDATION_LAYERS {
-    create_info.enabled_layers = VALIDATION_LAYERS.iter().map(|s| s.to_string()).collect();
+  if validation {
+    create_info.enabled_extensions.ext_debug_utils = true;
+    create_info.enabled_layers = vec![VALIDATION_LAYER.to_string()];
+  }
+
+  let instance = Instance::new(create_info)?;
+
+  let debug_callback = if validation {
+    match DebugCallback::new(
+      &instance,
+      // every message gets logged at a matching level, so that the log filter decides what shows
+      MessageSeverity::all(),
+      MessageType::all(),
+      print_message,
+    ) {
+      Ok(callback) => Some(callback),
+      Err(e) => {
+        warn!("Failed to create validation debug callback: {:?}", e);
+        None
+      }
+    }
+  } else {
+    None
+  };
+
+  Ok((instance, debug_callback))
+}
+
+/// Returns true if the validation layer is installed, otherwise logs the available layers
+fn validation_layer_available() -> bool {
+  let layers: Vec<String> = match vulkano::instance::layers_list() {
+    Ok(layers) => layers.map(|l| l.name().to_string()).collect(),
+    Err(e) => {
+      warn!("Validation disabled, failed to list layers: {:?}", e);
+      return false;
+    }
+  };
+
+  if layers.iter().any(|name| name == VALIDATION_LAYER) {
+    debug!("Available layers: {:?}", layers);
+    true
+  } else {
+    warn!(
+      "Validation disabled, {} is not installed. Available layers:\n {:?}",
+      VALIDATION_LAYER, layers
+    );
+    false
   }
+}
 
-  Instance::new(create_info).unwrap()
+/// Logs with the `vulkan` target, at the level that matches the message severity
+fn print_message(message: &Message) {
+  let layer = message.layer_prefix.unwrap_or("unknown");
+  let description = message.description;
+
+  if message.severity.error {
+    error!(target: "vulkan", "{}: {}", layer, description);
+  } else if message.severity.warning {
+    warn!(target: "vulkan", "{}: {}", layer, description);
+  } else if message.severity.information {
+    info!(target: "vulkan", "{}: {}", layer, description);
+  } else {
+    debug!(target: "vulkan", "{}: {}", layer, description);
+  }
 }
@@ -10,5 +10,6 @@ pub mod physical_device;
 pub mod pipeline;
 pub mod render_pass;
 pub mod swapchain;
+pub mod textures;
 
-pub use physical_device::{QueueFamilies, Queues};
\ No newline at end of file
+pub use physical_device::{DeviceChoice, QueueFamilies, Queues};
\ No newline at end of file
@@ -1,97 +1,522 @@
-use std::sync::Arc;
+use std::{fmt, sync::Arc};
 use vulkano::device::{Queue, QueueCreateInfo};
 
 use vulkano::{
   device::{
     physical::{PhysicalDevice, PhysicalDeviceType, QueueFamily},
-    DeviceExtensions,
+    DeviceExtensions, Features,
   },
   instance::Instance,
   swapchain::Surface,
 };
 use winit::window::Window;
 
+/// Device requested with `--device`, instead of letting [`select`] choose one
+#[derive(Clone, Debug)]
+pub enum DeviceChoice {
+  /// Position of the device in the list printed by [`print_devices`]
+  Index(usize),
+  /// Case insensitive part of the device name, the first matching device gets chosen
+  Name(String),
+}
+
+impl DeviceChoice {
+  /// Numbers are treated as indices and everything else as names
+  pub fn parse(value: &str) -> Self {
+    match value.parse() {
+      Ok(i) => DeviceChoice::Index(i),
+      Err(_) => DeviceChoice::Name(value.to_lowercase()),
+    }
+  }
+
+  fn matches(&self, index: usize, name: &str) -> bool {
+    match self {
+      DeviceChoice::Index(i) => *i == index,
+      DeviceChoice::Name(part) => name.to_lowercase().contains(part.as_str()),
+    }
+  }
+}
+
+/// Reasons why no physical device could be used
+#[derive(Debug)]
+pub enum DeviceSelectionError {
+  /// No device has the required extensions and features and a graphics queue that can present
+  NoSuitableDevice,
+  /// No device matches the one requested with `--device`
+  NotFound(DeviceChoice),
+  /// The requested device doesn't support the required extensions
+  MissingExtensions(String),
+  /// The requested device doesn't support the features in [`required_features`]
+  MissingFeatures(String),
+  /// The requested device doesn't have a graphics queue family that can present to the window
+  NoGraphicsQueue(String),
+}
+
+impl fmt::Display for DeviceSelectionError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      DeviceSelectionError::NoSuitableDevice => write!(f, "no suitable device available"),
+      DeviceSelectionError::NotFound(DeviceChoice::Index(i)) => {
+        write!(f, "no device with index {}, see --list-devices", i)
+      }
+      DeviceSelectionError::NotFound(DeviceChoice::Name(name)) => {
+        write!(f, "no device named \"{}\", see --list-devices", name)
+      }
+      DeviceSelectionError::MissingExtensions(name) => write!(
+        f,
+        "device \"{}\" doesn't support the required extensions",
+        name
+      ),
+      DeviceSelectionError::MissingFeatures(name) => write!(
+        f,
+        "device \"{}\" doesn't support the required features",
+        name
+      ),
+      DeviceSelectionError::NoGraphicsQueue(name) => write!(
+        f,
+        "device \"{}\" doesn't have a graphics queue that can present to the window",
+        name
+      ),
+    }
+  }
+}
+
 pub struct QueueFamilies<'a> {
   pub graphics: QueueFamily<'a>,
   pub compute: QueueFamily<'a>,
   pub transfers: QueueFamily<'a>,
+  pub mapping: QueueMapping,
 }
 
 pub struct Queues {
   pub graphics: Arc<Queue>,
   pub compute: Arc<Queue>,
   pub transfers: Arc<Queue>,
+  /// Family and index of each queue above, queues can be the same if the device doesn't have
+  /// enough of them
+  pub mapping: QueueMapping,
+}
+
+/// What a queue family can do, kept separate from vulkano so that [`choose_queues`] only
+/// depends on plain data
+#[derive(Clone, Copy, Debug)]
+pub struct QueueFamilyDescription {
+  pub id: u32,
+  pub queues_count: u32,
+  pub graphics: bool,
+  pub compute: bool,
+  /// Explicit support, families with graphics or compute can always do transfers
+  pub transfers: bool,
+  pub can_present: bool,
+}
+
+impl QueueFamilyDescription {
+  fn new(family: &QueueFamily, can_present: bool) -> Self {
+    Self {
+      id: family.id(),
+      queues_count: family.queues_count() as u32,
+      graphics: family.supports_graphics(),
+      compute: family.supports_compute(),
+      transfers: family.explicitly_supports_transfers(),
+      can_present,
+    }
+  }
+
+  fn supports_transfers(&self) -> bool {
+    self.transfers || self.graphics || self.compute
+  }
+}
+
+/// A single queue in a queue family
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QueueSlot {
+  pub family: u32,
+  pub index: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QueueMapping {
+  pub graphics: QueueSlot,
+  pub compute: QueueSlot,
+  pub transfers: QueueSlot,
+}
+
+impl QueueMapping {
+  /// Number of queues that need to be created in each family, ordered by family id
+  pub fn queue_counts(&self) -> Vec<(u32, u32)> {
+    let mut counts: Vec<(u32, u32)> = Vec::new();
+    for slot in [self.graphics, self.compute, self.transfers] {
+      match counts.iter_mut().find(|(family, _)| *family == slot.family) {
+        Some((_, count)) => *count = (*count).max(slot.index + 1),
+        None => counts.push((slot.family, slot.index + 1)),
+      }
+    }
+    counts.sort_unstable();
+    counts
+  }
+}
+
+/// Chooses a family for each kind of queue, or `None` if no family has graphics and can present.
+///
+/// Compute prefers a family without graphics (async compute) and transfers prefer a family that
+/// only supports transfers, falling back to any other family and then to the graphics one.
+/// Queues in the same family get different indices until the family runs out of them,
+/// after which the last one gets shared.
+pub fn choose_queues(families: &[QueueFamilyDescription]) -> Option<QueueMapping> {
+  let graphics = best_family(families, |f| {
+    if f.graphics && f.can_present {
+      Some(f.compute as u32)
+    } else {
+      None
+    }
+  })?;
+  let compute = best_family(families, |f| match (f.compute, f.graphics) {
+    (false, _) => None,
+    (true, false) => Some(2),
+    (true, true) if f.id != graphics.id => Some(1),
+    (true, true) => Some(0),
+  })
+  .unwrap_or(graphics);
+  let transfers = best_family(families, |f| {
+    if !f.supports_transfers() {
+      None
+    } else if !f.graphics && !f.compute {
+      Some(3)
+    } else if f.id != graphics.id && f.id != compute.id {
+      Some(2)
+    } else if f.id != graphics.id {
+      Some(1)
+    } else {
+      Some(0)
+    }
+  })
+  .unwrap_or(graphics);
+
+  let mut used: Vec<(u32, u32)> = Vec::new();
+  let mut next_slot = |family: &QueueFamilyDescription| {
+    let index = match used.iter_mut().find(|(id, _)| *id == family.id) {
+      Some((_, used_count)) => {
+        let index = (*used_count).min(family.queues_count - 1);
+        *used_count += 1;
+        index
+      }
+      None => {
+        used.push((family.id, 1));
+        0
+      }
+    };
+    QueueSlot {
+      family: family.id,
+      index,
+    }
+  };
+
+  Some(QueueMapping {
+    graphics: next_slot(graphics),
+    compute: next_slot(compute),
+    transfers: next_slot(transfers),
+  })
+}
+
+/// Family with the highest score, the first one wins ties
+fn best_family(
+  families: &[QueueFamilyDescription],
+  score: impl Fn(&QueueFamilyDescription) -> Option<u32>,
+) -> Option<&QueueFamilyDescription> {
+  let mut best: Option<(&QueueFamilyDescription, u32)> = None;
+  for family in families.iter().filter(|f| f.queues_count > 0) {
+    if let Some(family_score) = score(family) {
+      if best.map_or(true, |(_, best_score)| family_score > best_score) {
+        best = Some((family, family_score));
+      }
+    }
+  }
+  best.map(|(family, _)| family)
 }
 
 impl<'a> QueueFamilies<'a> {
   pub fn get_queue_create_info(&self) -> Vec<QueueCreateInfo<'a>> {
-    vec![
-      QueueCreateInfo::family(self.graphics),
-      QueueCreateInfo::family(self.compute),
-      QueueCreateInfo::family(self.transfers),
-    ]
+    let physical_device = self.graphics.physical_device();
+    self
+      .mapping
+      .queue_counts()
+      .into_iter()
+      .map(|(family, count)| QueueCreateInfo {
+        queues: vec![0.5; count as usize],
+        ..QueueCreateInfo::family(physical_device.queue_family_by_id(family).unwrap())
+      })
+      .collect()
   }
 
-  pub fn get_queues(iter: &mut (impl ExactSizeIterator + Iterator<Item = Arc<Queue>>)) -> Queues {
-    // same order as above
+  /// Takes the queues returned when creating the device with [`Self::get_queue_create_info`]
+  pub fn get_queues(&self, iter: impl Iterator<Item = Arc<Queue>>) -> Queues {
+    let queues: Vec<Arc<Queue>> = iter.collect();
+    let find = |slot: QueueSlot| {
+      queues
+        .iter()
+        .find(|q| q.family().id() == slot.family && q.id_within_family() == slot.index)
+        .expect("queue wasn't created")
+        .clone()
+    };
+
     Queues {
-      graphics: iter.next().unwrap(),
-      compute: iter.next().unwrap(),
-      transfers: iter.next().unwrap(),
+      graphics: find(self.mapping.graphics),
+      compute: find(self.mapping.compute),
+      transfers: find(self.mapping.transfers),
+      mapping: self.mapping,
     }
   }
 }
 
+/// Features that every selected device has to support:
+/// - `draw_indirect_first_instance`: indirect draw commands start at the first instance of
+///   their model
+pub fn required_features() -> Features {
+  Features {
+    draw_indirect_first_instance: true,
+    ..Features::none()
+  }
+}
+
+/// [`required_features`] together with the ones that are only used if the device supports them:
+/// - `fill_mode_non_solid`: wireframe view mode
+pub fn get_enabled_features(physical_device: PhysicalDevice) -> Features {
+  let supported = physical_device.supported_features();
+  Features {
+    fill_mode_non_solid: supported.fill_mode_non_solid,
+    ..required_features()
+  }
+}
+
 pub fn select<'a>(
   instance: &'a Arc<Instance>,
-  surface: Arc<Surface<Window>>,
+  surface: Arc<Surface<Arc<Window>>>,
   device_extensions: &DeviceExtensions,
-) -> (PhysicalDevice<'a>, QueueFamilies<'a>) {
-  let (physical_device, queue_family) = PhysicalDevice::enumerate(&instance)
-    .filter(|&p| p.supported_extensions().is_superset_of(&device_extensions))
-    .filter_map(|p| {
-      let mut graphics = None;
-      let mut compute = None;
-      let mut transfers = None;
-      for family in p.queue_families() {
-        if family.supports_graphics() && family.supports_surface(&surface).unwrap_or(false) {
-          graphics = Some(family);
-        } else if family.supports_compute() {
-          compute = Some(family)
-        } else if family.explicitly_supports_transfers() {
-          transfers = Some(family);
-        }
-      }
+  choice: Option<&DeviceChoice>,
+) -> Result<(PhysicalDevice<'a>, QueueFamilies<'a>), DeviceSelectionError> {
+  select_with(instance, device_extensions, choice, |family| {
+    family.supports_surface(&surface).unwrap_or(false)
+  })
+}
+
+/// Same as [`select`], but doesn't require the graphics family to be able to present
+pub fn select_headless<'a>(
+  instance: &'a Arc<Instance>,
+  device_extensions: &DeviceExtensions,
+  choice: Option<&DeviceChoice>,
+) -> Result<(PhysicalDevice<'a>, QueueFamilies<'a>), DeviceSelectionError> {
+  select_with(instance, device_extensions, choice, |_| true)
+}
 
-      if let Some(graphics) = graphics {
-        Some((
-          p,
-          QueueFamilies {
-            graphics,
-            compute: if let Some(family) = compute {
-              family
-            } else {
-              graphics
-            },
-            transfers: if let Some(family) = transfers {
-              family
-            } else {
-              graphics
-            },
-          },
-        ))
-      } else {
-        None
+/// Prints every device with the index used by [`DeviceChoice::Index`]
+pub fn print_devices(instance: &Arc<Instance>) {
+  for (i, p) in PhysicalDevice::enumerate(instance).enumerate() {
+    let properties = p.properties();
+    println!(
+      "{}: {} ({:?}, Vulkan {})",
+      i,
+      properties.device_name,
+      properties.device_type,
+      p.api_version()
+    );
+    for family in p.queue_families() {
+      let mut capabilities = Vec::new();
+      if family.supports_graphics() {
+        capabilities.push("graphics");
+      }
+      if family.supports_compute() {
+        capabilities.push("compute");
       }
-    })
-    .min_by_key(|(p, _)| match p.properties().device_type {
-      PhysicalDeviceType::DiscreteGpu => 0,
-      PhysicalDeviceType::IntegratedGpu => 1,
-      PhysicalDeviceType::VirtualGpu => 2,
-      PhysicalDeviceType::Cpu => 3,
-      PhysicalDeviceType::Other => 4,
-    })
-    .expect("no device available");
-
-  (physical_device, queue_family)
+      if family.explicitly_supports_transfers() {
+        capabilities.push("transfers");
+      }
+      println!(
+        "   queue family {}: {} queues, {}",
+        family.id(),
+        family.queues_count(),
+        capabilities.join(", ")
+      );
+    }
+  }
+}
+
+fn select_with<'a>(
+  instance: &'a Arc<Instance>,
+  device_extensions: &DeviceExtensions,
+  choice: Option<&DeviceChoice>,
+  can_present: impl Fn(&QueueFamily) -> bool,
+) -> Result<(PhysicalDevice<'a>, QueueFamilies<'a>), DeviceSelectionError> {
+  let choice = match choice {
+    Some(choice) => choice,
+    None => {
+      return PhysicalDevice::enumerate(&instance)
+        .filter(|&p| p.supported_extensions().is_superset_of(&device_extensions))
+        .filter(|&p| p.supported_features().is_superset_of(&required_features()))
+        .filter_map(|p| Some((p, find_queue_families(p, &can_present)?)))
+        .min_by_key(|(p, _)| match p.properties().device_type {
+          PhysicalDeviceType::DiscreteGpu => 0,
+          PhysicalDeviceType::IntegratedGpu => 1,
+          PhysicalDeviceType::VirtualGpu => 2,
+          PhysicalDeviceType::Cpu => 3,
+          PhysicalDeviceType::Other => 4,
+        })
+        .ok_or(DeviceSelectionError::NoSuitableDevice)
+    }
+  };
+
+  let physical_device = PhysicalDevice::enumerate(&instance)
+    .enumerate()
+    .find(|(i, p)| choice.matches(*i, &p.properties().device_name))
+    .map(|(_, p)| p)
+    .ok_or_else(|| DeviceSelectionError::NotFound(choice.clone()))?;
+  let name = physical_device.properties().device_name.clone();
+
+  if !physical_device
+    .supported_extensions()
+    .is_superset_of(&device_extensions)
+  {
+    return Err(DeviceSelectionError::MissingExtensions(name));
+  }
+
+  if !physical_device
+    .supported_features()
+    .is_superset_of(&required_features())
+  {
+    return Err(DeviceSelectionError::MissingFeatures(name));
+  }
+
+  let queue_families = find_queue_families(physical_device, &can_present)
+    .ok_or(DeviceSelectionError::NoGraphicsQueue(name))?;
+
+  Ok((physical_device, queue_families))
+}
+
+fn find_queue_families<'a>(
+  physical_device: PhysicalDevice<'a>,
+  can_present: &impl Fn(&QueueFamily) -> bool,
+) -> Option<QueueFamilies<'a>> {
+  let descriptions: Vec<QueueFamilyDescription> = physical_device
+    .queue_families()
+    .map(|family| QueueFamilyDescription::new(&family, can_present(&family)))
+    .collect();
+  let mapping = choose_queues(&descriptions)?;
+  let family = |slot: QueueSlot| physical_device.queue_family_by_id(slot.family).unwrap();
+
+  Some(QueueFamilies {
+    graphics: family(mapping.graphics),
+    compute: family(mapping.compute),
+    transfers: family(mapping.transfers),
+    mapping,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn family(
+    id: u32,
+    queues_count: u32,
+    graphics: bool,
+    compute: bool,
+    can_present: bool,
+  ) -> QueueFamilyDescription {
+    QueueFamilyDescription {
+      id,
+      queues_count,
+      graphics,
+      compute,
+      transfers: true,
+      can_present,
+    }
+  }
+
+  fn slot(family: u32, index: u32) -> QueueSlot {
+    QueueSlot { family, index }
+  }
+
+  fn assert_unique_families(mapping: &QueueMapping) {
+    let counts = mapping.queue_counts();
+    for (i, (family, _)) in counts.iter().enumerate() {
+      assert!(
+        counts[i + 1..].iter().all(|(other, _)| other != family),
+        "family {} appears more than once in {:?}",
+        family,
+        counts
+      );
+    }
+  }
+
+  #[test]
+  fn uses_dedicated_families() {
+    let families = [
+      family(0, 16, true, true, true),
+      family(1, 2, false, true, false),
+      family(2, 1, false, false, false),
+    ];
+    let mapping = choose_queues(&families).unwrap();
+
+    assert_eq!(mapping.graphics, slot(0, 0));
+    assert_eq!(mapping.compute, slot(1, 0));
+    assert_eq!(mapping.transfers, slot(2, 0));
+    assert_eq!(mapping.queue_counts(), vec![(0, 1), (1, 1), (2, 1)]);
+  }
+
+  #[test]
+  fn shares_the_only_queue() {
+    let mapping = choose_queues(&[family(0, 1, true, true, true)]).unwrap();
+
+    assert_eq!(mapping.graphics, slot(0, 0));
+    assert_eq!(mapping.compute, slot(0, 0));
+    assert_eq!(mapping.transfers, slot(0, 0));
+    assert_eq!(mapping.queue_counts(), vec![(0, 1)]);
+  }
+
+  #[test]
+  fn uses_separate_queues_in_a_single_family() {
+    let mapping = choose_queues(&[family(0, 3, true, true, true)]).unwrap();
+
+    assert_eq!(mapping.graphics, slot(0, 0));
+    assert_eq!(mapping.compute, slot(0, 1));
+    assert_eq!(mapping.transfers, slot(0, 2));
+    assert_eq!(mapping.queue_counts(), vec![(0, 3)]);
+  }
+
+  #[test]
+  fn requires_a_graphics_family_that_can_present() {
+    let families = [
+      family(0, 16, true, true, false),
+      family(1, 2, false, true, true),
+      family(2, 1, false, false, true),
+    ];
+
+    assert_eq!(choose_queues(&families), None);
+  }
+
+  #[test]
+  fn queue_counts_have_unique_families() {
+    let layouts = [
+      vec![family(0, 1, true, true, true)],
+      vec![family(0, 2, true, true, true)],
+      vec![family(0, 3, true, true, true)],
+      vec![
+        family(0, 1, true, true, true),
+        family(1, 1, false, true, false),
+      ],
+      vec![
+        family(0, 2, true, true, true),
+        family(1, 1, false, false, false),
+      ],
+      vec![
+        family(0, 1, false, false, false),
+        family(1, 1, true, false, true),
+        family(2, 4, false, true, false),
+      ],
+    ];
+
+    for families in layouts.iter() {
+      let mapping = choose_queues(families).unwrap();
+      assert_unique_families(&mapping);
+    }
+  }
 }
@@ -1,19 +1,24 @@
-use crate::render::vertex_data::{MatrixInstance, Vertex3d};
+use crate::render::{
+  shaders::debug,
+  vertex_data::{MatrixInstance, Vertex3d},
+};
 use std::sync::Arc;
 use vulkano::{
   device::Device,
+  image::SampleCount,
   pipeline::{
     graphics::{
-      depth_stencil::DepthStencilState,
+      depth_stencil::{CompareOp, DepthStencilState},
       input_assembly::InputAssemblyState,
-      rasterization::{CullMode, RasterizationState},
+      multisample::MultisampleState,
+      rasterization::{CullMode, PolygonMode, RasterizationState},
       vertex_input::BuffersDefinition,
       viewport::{Viewport, ViewportState},
     },
-    ComputePipeline, GraphicsPipeline,
+    ComputePipeline, GraphicsPipeline, StateMode,
   },
   render_pass::{RenderPass, Subpass},
-  shader::ShaderModule,
+  shader::{ShaderModule, SpecializationConstants},
 };
 
 pub fn create_graphics(
@@ -22,7 +27,95 @@ pub fn create_graphics(
   fs: Arc<ShaderModule>,
   render_pass: Arc<RenderPass>,
   viewport: Viewport,
+  reverse_z: bool,
+) -> Arc<GraphicsPipeline> {
+  build_graphics(
+    device,
+    vs,
+    fs,
+    (),
+    render_pass,
+    viewport,
+    reverse_z,
+    PolygonMode::Fill,
+  )
+}
+
+/// Same as [`create_graphics`], but only draws triangle edges.
+/// Needs the `fill_mode_non_solid` device feature.
+pub fn create_wireframe(
+  device: Arc<Device>,
+  vs: Arc<ShaderModule>,
+  fs: Arc<ShaderModule>,
+  render_pass: Arc<RenderPass>,
+  viewport: Viewport,
+  reverse_z: bool,
+) -> Arc<GraphicsPipeline> {
+  build_graphics(
+    device,
+    vs,
+    fs,
+    (),
+    render_pass,
+    viewport,
+    reverse_z,
+    PolygonMode::Line,
+  )
+}
+
+/// Creates a pipeline with the debug shaders, where `mode` selects what gets drawn
+/// (see `shaders/debug/fragment.glsl`)
+pub fn create_debug(
+  device: Arc<Device>,
+  vs: Arc<ShaderModule>,
+  fs: Arc<ShaderModule>,
+  render_pass: Arc<RenderPass>,
+  viewport: Viewport,
+  reverse_z: bool,
+  mode: u32,
+) -> Arc<GraphicsPipeline> {
+  build_graphics(
+    device,
+    vs,
+    fs,
+    debug::fs::SpecializationConstants {
+      mode,
+      reverse_z: reverse_z as u32,
+    },
+    render_pass,
+    viewport,
+    reverse_z,
+    PolygonMode::Fill,
+  )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn build_graphics<Fss: SpecializationConstants>(
+  device: Arc<Device>,
+  vs: Arc<ShaderModule>,
+  fs: Arc<ShaderModule>,
+  fs_constants: Fss,
+  render_pass: Arc<RenderPass>,
+  viewport: Viewport,
+  reverse_z: bool,
+  polygon_mode: PolygonMode,
 ) -> Arc<GraphicsPipeline> {
+  let mut depth_stencil_state = DepthStencilState::simple_depth_test();
+  if reverse_z {
+    // closer fragments have bigger depth values
+    depth_stencil_state.depth.as_mut().unwrap().compare_op = StateMode::Fixed(CompareOp::Greater);
+  }
+
+  let subpass = Subpass::from(render_pass, 0).unwrap();
+  // matches the samples of the render pass attachments
+  let multisample_state = MultisampleState {
+    rasterization_samples: subpass.num_samples().unwrap_or(SampleCount::Sample1),
+    ..Default::default()
+  };
+
+  let mut rasterization_state = RasterizationState::new().cull_mode(CullMode::Back);
+  rasterization_state.polygon_mode = polygon_mode;
+
   GraphicsPipeline::start()
     .vertex_input_state(
       BuffersDefinition::new()
@@ -32,10 +125,11 @@ pub fn create_graphics(
     .vertex_shader(vs.entry_point("main").unwrap(), ())
     .input_assembly_state(InputAssemblyState::new())
     .viewport_state(ViewportState::viewport_fixed_scissor_irrelevant([viewport]))
-    .rasterization_state(RasterizationState::new().cull_mode(CullMode::Back))
-    .depth_stencil_state(DepthStencilState::simple_depth_test())
-    .fragment_shader(fs.entry_point("main").unwrap(), ())
-    .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+    .rasterization_state(rasterization_state)
+    .depth_stencil_state(depth_stencil_state)
+    .multisample_state(multisample_state)
+    .fragment_shader(fs.entry_point("main").unwrap(), fs_constants)
+    .render_pass(subpass)
     .build(device.clone())
     // todo: reimplement dynamic uniforms
     // .with_auto_layout(device.clone(), |layout_create_infos| {
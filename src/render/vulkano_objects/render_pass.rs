@@ -1,30 +1,75 @@
 use std::sync::Arc;
 use vulkano::{
-  device::Device,
-  image::{traits::ImageAccess, AttachmentImage},
-  render_pass::RenderPass,
-  swapchain::Swapchain,
+  device::{physical::PhysicalDevice, Device},
+  format::{ClearValue, Format},
+  image::view::ImageViewAbstract,
+  render_pass::{Framebuffer, RenderPass},
 };
-use winit::window::Window;
 
+/// Depth formats in order of preference. Only D16_UNORM is guaranteed to be supported, but it
+/// has the least precision.
+const DEPTH_FORMATS: [Format; 4] = [
+  Format::D32_SFLOAT,
+  Format::D32_SFLOAT_S8_UINT,
+  Format::D24_UNORM_S8_UINT,
+  Format::D16_UNORM,
+];
+
+/// Creates a render pass with a color and a depth attachment.
+///
+/// With more than one sample, a multisampled color attachment gets drawn into instead and then
+/// resolved into the first one.
 pub fn create(
   device: Arc<Device>,
-  swapchain: Arc<Swapchain<Window>>,
-  depth_image: Arc<AttachmentImage>,
+  color_format: Format,
+  depth_format: Format,
+  samples: u32,
 ) -> Arc<RenderPass> {
+  if samples > 1 {
+    return vulkano::single_pass_renderpass!(
+      device.clone(),
+      attachments: {
+        color: {
+          load: DontCare,
+          store: Store,
+          format: color_format,
+          samples: 1,
+        },
+        depth: {
+          load: Clear,
+          store: DontCare,
+          format: depth_format,
+          samples: samples,
+        },
+        multisampled_color: {
+          load: Clear,
+          store: DontCare,
+          format: color_format,
+          samples: samples,
+        }
+      },
+      pass: {
+        color: [multisampled_color],
+        depth_stencil: {depth},
+        resolve: [color]
+      }
+    )
+    .unwrap();
+  }
+
   vulkano::single_pass_renderpass!(
     device.clone(),
     attachments: {
       color: {
         load: Clear,
         store: Store,
-        format: swapchain.image_format(),
+        format: color_format,
         samples: 1,
       },
       depth: {
         load: Clear,
         store: DontCare,
-        format: depth_image.format(),
+        format: depth_format,
         samples: 1,
       }
     },
@@ -35,3 +80,61 @@ pub fn create(
   )
   .unwrap()
 }
+
+/// Returns the biggest sample count up to `requested` that the device supports for both color
+/// and depth attachments
+pub fn choose_sample_count(physical_device: PhysicalDevice, requested: u32) -> u32 {
+  let properties = physical_device.properties();
+  let color = properties.framebuffer_color_sample_counts;
+  let depth = properties.framebuffer_depth_sample_counts;
+  let is_supported = |samples: u32| match samples {
+    8 => color.sample8 && depth.sample8,
+    4 => color.sample4 && depth.sample4,
+    2 => color.sample2 && depth.sample2,
+    _ => true,
+  };
+
+  [8, 4, 2, 1]
+    .into_iter()
+    .find(|&samples| samples <= requested && is_supported(samples))
+    .unwrap()
+}
+
+/// Returns the first depth format that can be used as a depth attachment with optimal tiling
+pub fn choose_depth_format(physical_device: PhysicalDevice) -> Format {
+  DEPTH_FORMATS
+    .into_iter()
+    .find(|&format| {
+      physical_device
+        .format_properties(format)
+        .optimal_tiling_features
+        .depth_stencil_attachment
+    })
+    .expect("failed to find a supported depth format")
+}
+
+/// Clear values for every attachment of framebuffers that use a render pass made with [`create`]
+pub fn clear_values(framebuffer: &Framebuffer, reverse_z: bool) -> Vec<ClearValue> {
+  let attachments = framebuffer.attachments();
+  // the second attachment is always the depth one
+  let depth = depth_clear_value(attachments[1].format().unwrap(), reverse_z);
+  let color = ClearValue::Float([0.1, 0.1, 0.1, 1.0]);
+
+  if attachments.len() > 2 {
+    // the first one only gets resolved into
+    vec![ClearValue::None, depth, color]
+  } else {
+    vec![color, depth]
+  }
+}
+
+/// Value that clears depth attachments to the farthest depth (0 with reverse-Z),
+/// which also needs a stencil value if the format has one
+pub fn depth_clear_value(depth_format: Format, reverse_z: bool) -> ClearValue {
+  let depth = if reverse_z { 0.0 } else { 1.0 };
+  if depth_format.aspects().stencil {
+    ClearValue::DepthStencil((depth, 0))
+  } else {
+    ClearValue::Depth(depth)
+  }
+}
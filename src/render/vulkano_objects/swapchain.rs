@@ -1,39 +1,121 @@
+use crate::render::RenderError;
+use log::warn;
 use std::sync::Arc;
 use vulkano::{
   device::{physical::PhysicalDevice, Device},
+  format::Format,
   image::{ImageUsage, SwapchainImage},
-  swapchain::{Surface, Swapchain, SwapchainCreateInfo},
+  swapchain::{ColorSpace, PresentMode, Surface, Swapchain, SwapchainCreateInfo},
 };
+
+/// Formats where the hardware converts linear shader output to sRGB, in order of preference
+const SRGB_FORMATS: [(Format, ColorSpace); 2] = [
+  (Format::B8G8R8A8_SRGB, ColorSpace::SrgbNonLinear),
+  (Format::R8G8B8A8_SRGB, ColorSpace::SrgbNonLinear),
+];
+
+/// Floating point format that takes linear values, which can go over 1.0 on HDR displays.
+///
+/// HDR10 (`Hdr10St2084`) isn't used, as shaders would have to encode their output with the PQ curve.
+const HDR_FORMAT: (Format, ColorSpace) =
+  (Format::R16G16B16A16_SFLOAT, ColorSpace::ExtendedSrgbLinear);
 use winit::window::Window;
 
 pub fn create(
   physical_device: &PhysicalDevice,
   device: Arc<Device>,
-  surface: Arc<Surface<Window>>,
-) -> (Arc<Swapchain<Window>>, Vec<Arc<SwapchainImage<Window>>>) {
-  let caps = physical_device
-    .surface_capabilities(&surface, Default::default())
-    .expect("failed to get surface capabilities");
+  surface: Arc<Surface<Arc<Window>>>,
+  present_mode: PresentMode,
+  hdr: bool,
+) -> Result<
+  (
+    Arc<Swapchain<Arc<Window>>>,
+    Vec<Arc<SwapchainImage<Arc<Window>>>>,
+  ),
+  RenderError,
+> {
+  let caps = physical_device.surface_capabilities(&surface, Default::default())?;
 
+  // every surface supports at least one
   let composite_alpha = caps.supported_composite_alpha.iter().next().unwrap();
-  let image_format = Some(
-    physical_device
-      .surface_formats(&surface, Default::default())
-      .unwrap()[0]
-      .0,
-  );
+  let (image_format, image_color_space) = choose_surface_format(physical_device, &surface, hdr)?;
+  let present_mode = choose_present_mode(physical_device, &surface, present_mode)?;
 
-  Swapchain::new(
+  Ok(Swapchain::new(
     device,
     surface.clone(),
     SwapchainCreateInfo {
       min_image_count: caps.min_image_count + 1,
-      image_format,
+      image_format: Some(image_format),
+      image_color_space,
       image_extent: surface.window().inner_size().into(),
       image_usage: ImageUsage::color_attachment(),
       composite_alpha,
+      present_mode,
       ..Default::default()
     },
+  )?)
+}
+
+/// Returns the HDR format if it was requested and the surface supports it, otherwise the first
+/// supported sRGB format.
+///
+/// If there are none, the first format with a non linear sRGB color space gets chosen
+/// (or just the first one), in which case colors will probably look too dark.
+pub fn choose_surface_format(
+  physical_device: &PhysicalDevice,
+  surface: &Surface<Arc<Window>>,
+  hdr: bool,
+) -> Result<(Format, ColorSpace), RenderError> {
+  let supported = physical_device.surface_formats(surface, Default::default())?;
+
+  if hdr {
+    if supported.contains(&HDR_FORMAT) {
+      return Ok(HDR_FORMAT);
+    }
+    warn!("HDR was requested, but the surface doesn't support it");
+  }
+
+  if let Some(&format) = SRGB_FORMATS
+    .iter()
+    .find(|format| supported.contains(format))
+  {
+    return Ok(format);
+  }
+
+  // every surface supports at least one format
+  let format = supported
+    .iter()
+    .find(|(_, color_space)| *color_space == ColorSpace::SrgbNonLinear)
+    .unwrap_or(&supported[0]);
+  warn!(
+    "The surface doesn't support any sRGB format, using {:?}",
+    format
+  );
+  Ok(*format)
+}
+
+/// Returns `preferred` if the surface supports it, otherwise the closest supported mode.
+///
+/// Mailbox and Immediate both don't wait for vertical blanks, so they replace each other.
+/// FIFO is always supported, so it's used when nothing else is.
+pub fn choose_present_mode(
+  physical_device: &PhysicalDevice,
+  surface: &Surface<Arc<Window>>,
+  preferred: PresentMode,
+) -> Result<PresentMode, RenderError> {
+  let supported: Vec<PresentMode> = physical_device.surface_present_modes(surface)?.collect();
+
+  let alternative = match preferred {
+    PresentMode::Mailbox => PresentMode::Immediate,
+    PresentMode::Immediate => PresentMode::Mailbox,
+    _ => PresentMode::Fifo,
+  };
+
+  Ok(
+    [preferred, alternative]
+      .into_iter()
+      .find(|mode| supported.contains(mode))
+      .unwrap_or(PresentMode::Fifo),
   )
-  .unwrap()
 }
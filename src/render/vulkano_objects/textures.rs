@@ -0,0 +1,102 @@
+use crate::render::{models::Model, RenderError};
+use std::{collections::HashMap, sync::Arc};
+use vulkano::{
+  buffer::BufferContents,
+  command_buffer::{CommandBufferExecFuture, PrimaryAutoCommandBuffer},
+  descriptor_set::{layout::DescriptorSetLayout, PersistentDescriptorSet, WriteDescriptorSet},
+  device::{Device, Queue},
+  format::Format,
+  image::{view::ImageView, ImageDimensions, ImmutableImage, MipmapsCount},
+  sampler::{Sampler, SamplerCreateInfo},
+  sync::{GpuFuture, NowFuture},
+};
+
+/// Images sampled by the textured pipeline.
+///
+/// Models that use the same texture form a group, which shares a single image and descriptor set.
+pub struct Textures {
+  /// Set 1 of the textured pipeline, one for each group
+  pub descriptor_sets: Vec<Arc<PersistentDescriptorSet>>,
+  /// Group of each model, None if the model has no texture
+  pub model_groups: Vec<Option<usize>>,
+}
+
+impl Textures {
+  /// Uploads every texture used by the models and waits for each transfer to finish.
+  ///
+  /// Mipmaps are generated with blits, so the queue needs to support graphics operations.
+  pub fn load<V: BufferContents>(
+    device: Arc<Device>,
+    graphics_queue: Arc<Queue>,
+    layout: Arc<DescriptorSetLayout>,
+    models: &Vec<Box<dyn Model<V>>>,
+  ) -> Result<Self, RenderError> {
+    let sampler = Sampler::new(device, SamplerCreateInfo::simple_repeat_linear()).unwrap();
+
+    let mut descriptor_sets = Vec::new();
+    let mut group_keys: HashMap<String, usize> = HashMap::new();
+    let mut model_groups = Vec::with_capacity(models.len());
+
+    for model in models.iter() {
+      let texture = match model.get_texture() {
+        Some(texture) => texture,
+        None => {
+          model_groups.push(None);
+          continue;
+        }
+      };
+
+      let key = texture.key();
+      if let Some(&group) = group_keys.get(&key) {
+        model_groups.push(Some(group));
+        continue;
+      }
+
+      let (dimensions, pixels) = texture.load().map_err(|error| RenderError::ModelLoad {
+        name: format!("texture \"{}\"", key),
+        error,
+      })?;
+      let (image, future) = create_immutable_image(graphics_queue.clone(), dimensions, pixels);
+      future.then_signal_fence_and_flush()?.wait(None)?;
+
+      descriptor_sets.push(PersistentDescriptorSet::new(
+        layout.clone(),
+        [WriteDescriptorSet::image_view_sampler(
+          0,
+          ImageView::new_default(image).unwrap(),
+          sampler.clone(),
+        )],
+      )?);
+
+      group_keys.insert(key, descriptor_sets.len() - 1);
+      model_groups.push(Some(descriptor_sets.len() - 1));
+    }
+
+    Ok(Self {
+      descriptor_sets,
+      model_groups,
+    })
+  }
+}
+
+fn create_immutable_image(
+  queue: Arc<Queue>,
+  [width, height]: [u32; 2],
+  pixels: Vec<u8>,
+) -> (
+  Arc<ImmutableImage>,
+  CommandBufferExecFuture<NowFuture, PrimaryAutoCommandBuffer>,
+) {
+  ImmutableImage::from_iter(
+    pixels,
+    ImageDimensions::Dim2d {
+      width,
+      height,
+      array_layers: 1,
+    },
+    MipmapsCount::Log2,
+    Format::R8G8B8A8_SRGB,
+    queue,
+  )
+  .unwrap()
+}
@@ -1,20 +1,130 @@
 use crate::{
-  game_objects::{Cube, Renderable3dObject, Square},
+  game_objects::{
+    load_gltf_nodes, Cube, Lighting, LoadedModel, ModelSource, ObjectHandle, ObjectHandles,
+    ObjectLocation, Renderable3dObject, SceneObject, Square,
+  },
+  render::{ModelLoadError, ObjModel},
   GENERATE_CUBES,
 };
 use cgmath::{Euler, Point3, Rad};
+use log::info;
 use rand::Rng;
-use std::f32::consts::PI;
+use serde::{Deserialize, Serialize};
+use std::{
+  f32::consts::PI,
+  fmt,
+  fs::File,
+  io::{BufReader, BufWriter, Read, Write},
+  path::Path,
+};
+
+/// Written at the start of binary scene files, followed by [`BINARY_FORMAT_VERSION`]
+const BINARY_MAGIC: [u8; 4] = *b"SCNE";
+
+/// Bincode doesn't store field names, so unlike RON, files can't be read when the saved types
+/// change. This has to be increased every time that happens, with the previous layout kept
+/// and converted in [`read_binary`] so that older files can still be loaded.
+const BINARY_FORMAT_VERSION: u32 = 1;
+
+/// File formats that a scene can be saved to, chosen by the file extension
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SceneFormat {
+  /// Compact binary format (`bincode`), used for any extension other than `.ron`
+  Binary,
+  /// Human readable format (`.ron`)
+  Ron,
+}
+
+impl SceneFormat {
+  pub fn from_path(path: &Path) -> Self {
+    match path.extension().and_then(|e| e.to_str()) {
+      Some("ron") => SceneFormat::Ron,
+      _ => SceneFormat::Binary,
+    }
+  }
+}
+
+#[derive(Debug)]
+pub enum SceneFileError {
+  Io(std::io::Error),
+  Binary(bincode::Error),
+  RonSerialize(ron::Error),
+  RonDeserialize(ron::de::SpannedError),
+  /// The binary file doesn't start with a format header, so it was written before they existed
+  /// or it isn't a scene at all
+  MissingHeader,
+  /// The binary file was written by a newer version
+  UnsupportedVersion(u32),
+}
+
+impl fmt::Display for SceneFileError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      SceneFileError::Io(e) => write!(f, "io error: {}", e),
+      SceneFileError::Binary(e) => write!(f, "invalid binary scene: {}", e),
+      SceneFileError::RonSerialize(e) => write!(f, "failed to write scene: {}", e),
+      SceneFileError::RonDeserialize(e) => write!(f, "invalid scene: {}", e),
+      SceneFileError::MissingHeader => write!(
+        f,
+        "binary scene has no format header, it's either too old to be loaded or not a scene"
+      ),
+      SceneFileError::UnsupportedVersion(version) => write!(
+        f,
+        "binary scene has format version {}, but at most {} is supported",
+        version, BINARY_FORMAT_VERSION
+      ),
+    }
+  }
+}
+
+impl From<std::io::Error> for SceneFileError {
+  fn from(e: std::io::Error) -> Self {
+    SceneFileError::Io(e)
+  }
+}
+
+/// Part of [`Scene`] that gets saved to files
+///
+/// Defaults only help with RON files, binary ones have to change [`BINARY_FORMAT_VERSION`].
+#[derive(Deserialize)]
+struct SceneData {
+  cubes: Vec<Cube>,
+  squares: Vec<Square>,
+  #[serde(default)]
+  loaded_models: Vec<LoadedModel>,
+  #[serde(default)]
+  lighting: Lighting,
+}
+
+/// Same as [`SceneData`], but borrows objects so that saving doesn't need to clone them
+#[derive(Serialize)]
+struct SceneDataRef<'a> {
+  cubes: &'a Vec<Cube>,
+  squares: &'a Vec<Square>,
+  loaded_models: &'a Vec<LoadedModel>,
+  lighting: &'a Lighting,
+}
 
 /// Contains game objects not directly related to rendering
+///
+/// Objects can only be added and removed with [`Scene::spawn`] and [`Scene::despawn`],
+/// so that their handles stay valid. Every object, including the ones that the scene was
+/// created or loaded with, has a handle.
 pub struct Scene {
-  pub cubes: Vec<Cube>,
-  pub squares: Vec<Square>,
+  cubes: Vec<Cube>,
+  squares: Vec<Square>,
+  /// Models loaded from files, drawn after all cubes and squares
+  loaded_models: Vec<LoadedModel>,
+  pub lighting: Lighting,
+  /// Set when objects or lights change, so that their data gets uploaded again.
+  /// Gets reset by [`crate::App`] after each frame
   pub objects_changed: bool,
   pub total_object_count: usize,
+  handles: ObjectHandles,
 }
 
 impl Scene {
+  /// Creates the default scene, with randomly generated cubes if [`GENERATE_CUBES`] is set
   pub fn load() -> Self {
     let cubes = if GENERATE_CUBES == None {
       vec![
@@ -26,16 +136,198 @@ impl Scene {
     };
     let squares = vec![Square::new()];
 
-    let total_object_count = cubes.len() + squares.len();
+    Self::from_objects(cubes, squares, Vec::new(), Lighting::default())
+  }
+
+  /// Loads a scene previously saved with [`Scene::save_to`]
+  pub fn load_from(path: impl AsRef<Path>) -> Result<Self, SceneFileError> {
+    let path = path.as_ref();
+    let reader = BufReader::new(File::open(path)?);
+
+    let data: SceneData = match SceneFormat::from_path(path) {
+      SceneFormat::Binary => read_binary(reader)?,
+      SceneFormat::Ron => ron::de::from_reader(reader).map_err(SceneFileError::RonDeserialize)?,
+    };
+
+    Ok(Self::from_objects(
+      data.cubes,
+      data.squares,
+      data.loaded_models,
+      data.lighting,
+    ))
+  }
+
+  /// Saves all scene objects, in a format depending on the file extension (see [`SceneFormat`])
+  pub fn save_to(&self, path: impl AsRef<Path>) -> Result<(), SceneFileError> {
+    let path = path.as_ref();
+    let writer = BufWriter::new(File::create(path)?);
+
+    let data = SceneDataRef {
+      cubes: &self.cubes,
+      squares: &self.squares,
+      loaded_models: &self.loaded_models,
+      lighting: &self.lighting,
+    };
+
+    match SceneFormat::from_path(path) {
+      SceneFormat::Binary => write_binary(writer, &data),
+      SceneFormat::Ron => ron::ser::to_writer_pretty(writer, &data, ron::ser::PrettyConfig::new())
+        .map_err(SceneFileError::RonSerialize),
+    }
+  }
+
+  /// Creates a handle for every object, see [`Scene::cube_handles`] and [`Scene::square_handles`]
+  fn from_objects(
+    cubes: Vec<Cube>,
+    squares: Vec<Square>,
+    loaded_models: Vec<LoadedModel>,
+    lighting: Lighting,
+  ) -> Self {
+    let total_object_count = cubes.len()
+      + squares.len()
+      + loaded_models
+        .iter()
+        .map(|model| model.objects.len())
+        .sum::<usize>();
+
+    let mut handles = ObjectHandles::default();
+    for i in 0..cubes.len() {
+      handles.push(ObjectLocation::Cube(i));
+    }
+    for i in 0..squares.len() {
+      handles.push(ObjectLocation::Square(i));
+    }
+    for (model_i, model) in loaded_models.iter().enumerate() {
+      push_loaded_handles(&mut handles, model_i, model);
+    }
 
     Scene {
       cubes,
       squares,
+      loaded_models,
+      lighting,
       objects_changed: true,
       total_object_count,
+      handles,
     }
   }
 
+  /// Adds an object to the scene, returning a handle that stays valid until it gets despawned
+  ///
+  /// Panics if the object uses a loaded model that doesn't exist.
+  pub fn spawn(&mut self, object: SceneObject) -> ObjectHandle {
+    let location = match object {
+      SceneObject::Cube(cube) => {
+        self.cubes.push(cube);
+        ObjectLocation::Cube(self.cubes.len() - 1)
+      }
+      SceneObject::Square(square) => {
+        self.squares.push(square);
+        ObjectLocation::Square(self.squares.len() - 1)
+      }
+      SceneObject::Loaded { model, object } => {
+        let objects = &mut self
+          .loaded_models
+          .get_mut(model)
+          .unwrap_or_else(|| panic!("There is no loaded model with index {}", model))
+          .objects;
+        objects.push(object);
+        ObjectLocation::Loaded {
+          model,
+          index: objects.len() - 1,
+        }
+      }
+    };
+
+    self.objects_changed = true;
+    self.total_object_count += 1;
+    self.handles.push(location)
+  }
+
+  /// Removes an object from the scene and returns it, or None if it was already removed
+  pub fn despawn(&mut self, handle: ObjectHandle) -> Option<SceneObject> {
+    let object = match self.handles.swap_remove(handle)? {
+      ObjectLocation::Cube(i) => SceneObject::Cube(self.cubes.swap_remove(i)),
+      ObjectLocation::Square(i) => SceneObject::Square(self.squares.swap_remove(i)),
+      ObjectLocation::Loaded { model, index } => SceneObject::Loaded {
+        model,
+        object: self.loaded_models[model].objects.swap_remove(index),
+      },
+    };
+
+    self.objects_changed = true;
+    self.total_object_count -= 1;
+    Some(object)
+  }
+
+  /// Returns the transformation of a spawned object, or None if it was despawned
+  pub fn get_object_mut(&mut self, handle: ObjectHandle) -> Option<&mut Renderable3dObject> {
+    let object = match self.handles.get(handle)? {
+      ObjectLocation::Cube(i) => self.cubes[i].as_renderable(),
+      ObjectLocation::Square(i) => &mut self.squares[i].object,
+      ObjectLocation::Loaded { model, index } => &mut self.loaded_models[model].objects[index],
+    };
+
+    self.objects_changed = true;
+    Some(object)
+  }
+
+  /// Adds a model loaded from a file with a single object, returning the model index.
+  ///
+  /// Models get uploaded when the renderer is created, so this should be called before that.
+  pub fn add_loaded_model(&mut self, source: ModelSource, object: Renderable3dObject) -> usize {
+    let mut model = LoadedModel::new(source);
+    model.objects.push(object);
+    push_loaded_handles(&mut self.handles, self.loaded_models.len(), &model);
+    self.loaded_models.push(model);
+
+    self.objects_changed = true;
+    self.total_object_count += 1;
+    self.loaded_models.len() - 1
+  }
+
+  /// Adds every mesh used in a glTF file as a model, with one object for each node that uses it.
+  ///
+  /// Models get uploaded when the renderer is created, so this should be called before that.
+  pub fn import_gltf(&mut self, path: impl AsRef<Path>) -> Result<(), ModelLoadError> {
+    let models = load_gltf_nodes(path.as_ref())?;
+
+    self.total_object_count += models
+      .iter()
+      .map(|model| model.objects.len())
+      .sum::<usize>();
+    for model in models {
+      push_loaded_handles(&mut self.handles, self.loaded_models.len(), &model);
+      self.loaded_models.push(model);
+    }
+    self.objects_changed = true;
+
+    Ok(())
+  }
+
+  /// Adds every object / group of an OBJ file that contains faces as a model, each with a copy
+  /// of `object`.
+  ///
+  /// Models get uploaded when the renderer is created, so this should be called before that.
+  pub fn import_obj(
+    &mut self,
+    path: impl AsRef<Path>,
+    object: Renderable3dObject,
+  ) -> Result<(), ModelLoadError> {
+    let path = path.as_ref();
+    for name in ObjModel::load_group_names(path)? {
+      self.add_loaded_model(
+        ModelSource::Obj {
+          path: path.to_path_buf(),
+          group: Some(name),
+        },
+        object.clone(),
+      );
+    }
+
+    Ok(())
+  }
+
   fn get_random_cubes() -> Vec<Cube> {
     let gen_length = if let Some(value) = GENERATE_CUBES {
       value
@@ -43,13 +335,13 @@ impl Scene {
       panic!()
     };
 
-    println!("Generating cubes...");
+    info!("Generating cubes...");
     let mut rng = rand::thread_rng();
     let mut cubes: Vec<Cube> = Vec::with_capacity(gen_length * gen_length * gen_length);
     for i in 0..gen_length {
       for j in 0..gen_length {
         for k in 0..gen_length {
-          cubes.push(Cube::from_full(Renderable3dObject::from_full(
+          let mut cube = Cube::from_full(Renderable3dObject::from_full(
             Point3::new(
               i as f32 - 0.5 + rng.gen::<f32>(),
               j as f32 - 0.5 + rng.gen::<f32>(),
@@ -61,7 +353,9 @@ impl Scene {
               Rad(rng.gen_range(-PI..PI)),
             ),
             0.15,
-          )))
+          ));
+          cube.change_to_random_color();
+          cubes.push(cube);
         }
       }
     }
@@ -69,13 +363,133 @@ impl Scene {
     cubes
   }
 
-  pub fn get_cube_mut(&mut self, i: usize) -> &mut Cube {
-    self.objects_changed = true;
-    &mut self.cubes[i]
+  pub fn cubes(&self) -> &[Cube] {
+    &self.cubes
   }
 
-  pub fn get_square_mut(&mut self, i: usize) -> &mut Square {
-    self.objects_changed = true;
-    &mut self.squares[i]
+  pub fn squares(&self) -> &[Square] {
+    &self.squares
+  }
+
+  pub fn loaded_models(&self) -> &[LoadedModel] {
+    &self.loaded_models
+  }
+
+  /// Handles of every cube, in the same order as [`Scene::cubes`]
+  pub fn cube_handles(&self) -> &[ObjectHandle] {
+    self.handles.cubes()
+  }
+
+  /// Handles of every square, in the same order as [`Scene::squares`]
+  pub fn square_handles(&self) -> &[ObjectHandle] {
+    self.handles.squares()
+  }
+
+  /// Returns a square, or None if it was despawned or the handle belongs to another object
+  pub fn get_square_mut(&mut self, handle: ObjectHandle) -> Option<&mut Square> {
+    match self.handles.get(handle)? {
+      ObjectLocation::Square(i) => {
+        self.objects_changed = true;
+        Some(&mut self.squares[i])
+      }
+      _ => None,
+    }
+  }
+}
+
+/// Writes the format header followed by the scene
+fn write_binary(mut writer: impl Write, data: &SceneDataRef) -> Result<(), SceneFileError> {
+  writer.write_all(&BINARY_MAGIC)?;
+  bincode::serialize_into(&mut writer, &BINARY_FORMAT_VERSION).map_err(SceneFileError::Binary)?;
+  bincode::serialize_into(writer, data).map_err(SceneFileError::Binary)
+}
+
+/// Checks the format header and reads the scene. Layouts of older versions get converted here.
+fn read_binary(mut reader: impl Read) -> Result<SceneData, SceneFileError> {
+  let mut magic = [0; 4];
+  match reader.read_exact(&mut magic) {
+    Ok(()) if magic == BINARY_MAGIC => {}
+    Ok(()) => return Err(SceneFileError::MissingHeader),
+    Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+      return Err(SceneFileError::MissingHeader)
+    }
+    Err(e) => return Err(e.into()),
+  }
+
+  let version: u32 = bincode::deserialize_from(&mut reader).map_err(SceneFileError::Binary)?;
+  match version {
+    BINARY_FORMAT_VERSION => bincode::deserialize_from(reader).map_err(SceneFileError::Binary),
+    version => Err(SceneFileError::UnsupportedVersion(version)),
+  }
+}
+
+fn push_loaded_handles(handles: &mut ObjectHandles, model_i: usize, model: &LoadedModel) {
+  for index in 0..model.objects.len() {
+    handles.push(ObjectLocation::Loaded {
+      model: model_i,
+      index,
+    });
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn write_scene(cubes: Vec<Cube>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let data = SceneDataRef {
+      cubes: &cubes,
+      squares: &vec![Square::new()],
+      loaded_models: &Vec::new(),
+      lighting: &Lighting::default(),
+    };
+    write_binary(&mut bytes, &data).unwrap();
+    bytes
+  }
+
+  #[test]
+  fn binary_round_trip() {
+    let mut cube = Cube::new(Point3::new(1.0, 2.0, 3.0));
+    cube.color = [0.1, 0.2, 0.3];
+    let bytes = write_scene(vec![cube]);
+
+    let data = read_binary(bytes.as_slice()).unwrap();
+    assert_eq!(data.cubes.len(), 1);
+    assert_eq!(data.cubes[0].color, [0.1, 0.2, 0.3]);
+    assert_eq!(data.squares.len(), 1);
+  }
+
+  #[test]
+  fn rejects_files_without_header() {
+    // files written before the header existed start with the number of cubes
+    let data = SceneDataRef {
+      cubes: &vec![Cube::new(Point3::new(0.0, 0.0, 0.0))],
+      squares: &Vec::new(),
+      loaded_models: &Vec::new(),
+      lighting: &Lighting::default(),
+    };
+    let bytes = bincode::serialize(&data).unwrap();
+
+    for bytes in [&bytes[..], &[], &BINARY_MAGIC[..2]] {
+      assert!(matches!(
+        read_binary(bytes),
+        Err(SceneFileError::MissingHeader)
+      ));
+    }
+  }
+
+  #[test]
+  fn rejects_newer_versions() {
+    let mut bytes = write_scene(Vec::new());
+    let version = bincode::serialize(&(BINARY_FORMAT_VERSION + 1)).unwrap();
+    bytes[BINARY_MAGIC.len()..BINARY_MAGIC.len() + version.len()].copy_from_slice(&version);
+
+    match read_binary(bytes.as_slice()) {
+      Err(SceneFileError::UnsupportedVersion(version)) => {
+        assert_eq!(version, BINARY_FORMAT_VERSION + 1)
+      }
+      _ => panic!("newer versions should be rejected"),
+    }
   }
 }